@@ -6,13 +6,15 @@ extern crate core;
 #[ink::contract(env = pink_extension::PinkEnvironment)]
 mod lucky_raffle {
 
-    use alloc::{string::String, string::ToString, vec::Vec};
+    use alloc::{collections::BTreeMap, string::String, string::ToString, vec::Vec};
     use ink::storage::Lazy;
     use phat_offchain_rollup::clients::ink::{Action, ContractId, InkRollupClient};
     use pink_extension::chain_extension::signing;
-    use pink_extension::{error, info, ResultExt};
+    use pink_extension::{debug, error, info};
     use scale::{Decode, Encode};
-    use sp_core::crypto::{AccountId32, Ss58AddressFormatRegistry, Ss58Codec};
+    use serde::Deserialize;
+    use sp_core::crypto::{AccountId32, Ss58AddressFormat, Ss58AddressFormatRegistry, Ss58Codec};
+    use sp_core::U256;
 
     type CodeHash = [u8; 32];
 
@@ -51,6 +53,23 @@ mod lucky_raffle {
         attest_key: [u8; 32],
         /// The JS code that processes the rollup queue request
         core_js: Lazy<CoreJs>,
+        /// new owner proposed by the current owner, waiting for the acceptance
+        pending_owner: Lazy<Option<AccountId>>,
+        /// settings added after the first version of the config
+        config_ext: Lazy<ConfigExt>,
+        /// Accounts never eligible to win
+        blocklist: Lazy<Vec<AccountId>>,
+        /// Accounts allowed to run the raffle when the triggers are restricted
+        triggers: Lazy<Vec<AccountId>>,
+    }
+
+    /// Event emitted when the core js (script and/or settings) is configured
+    #[ink(event)]
+    pub struct CoreJsConfigured {
+        /// hash of the js script
+        code_hash: CodeHash,
+        /// hash of the settings of js
+        settings_hash: CodeHash,
     }
 
     #[derive(Encode, Decode, Debug, Clone)]
@@ -85,7 +104,166 @@ mod lucky_raffle {
         sender_key: Option<[u8; 32]>,
     }
 
-    #[derive(Encode, Decode, Debug)]
+    /// Settings added after the first version of the contract.
+    /// They are stored apart from `Config` so the storage of a deployed contract can still be decoded.
+    #[derive(Encode, Debug, Default)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    struct ConfigExt {
+        /// Maximum rewards accepted in the output of the js. None for no limit.
+        max_rewards: Option<Balance>,
+        /// The endpoint publishing the official code hash of the core js
+        graph_api_url: Option<String>,
+        /// When true, the raffle runs only if the core js matches the code hash published by the registry
+        enforce_registry: bool,
+        /// Maximum length (in bytes) of the js error returned. None to use the default length.
+        max_js_error_len: Option<u32>,
+        /// When true, the js must return bytes (a string output is rejected)
+        strict_bytes_output: bool,
+        /// The RPC endpoint used when the primary one fails
+        fallback_rpc: Option<String>,
+        /// When true, the owner can evaluate any js with the message `eval_js`
+        allow_eval_js: bool,
+        /// The algorithm used to hash the core js and the request
+        hash_algo: HashAlgo,
+        /// When true, the raffle fails if the js doesn't provide any rewards for the era
+        require_rewards: bool,
+        /// Maximum length (in bytes) of the script and the arguments given to the js. None for no limit.
+        /// `phat_js::eval` doesn't support a time limit, so the size of the input is bounded instead.
+        max_js_input_len: Option<u32>,
+        /// SS58 prefix used to encode the addresses given to the js. None to use the Astar prefix.
+        ss58_prefix: Option<u16>,
+        /// When true, the addresses returned by the js must be encoded with the SS58 prefix
+        strict_ss58: bool,
+        /// When true, the participants are read from the rollup anchor and given to the js
+        read_participants_from_anchor: bool,
+        /// Maximum length (in bytes) of the output of the js. None for no limit.
+        max_js_output_bytes: Option<u32>,
+        /// When true, the rewards are read from the rollup anchor and given to the js
+        include_rewards_in_request: bool,
+        /// When true, only the accounts in the triggers list can run the raffle
+        restrict_triggers: bool,
+        /// Number of decimals of the token used to display the rewards. None to use the default (18)
+        token_decimals: Option<u8>,
+        /// Number of winners used when it is not set in the rollup anchor
+        default_nb_winners: Option<u16>,
+        /// Verbosity of the logs (0: off, 1: error, 2: info, 3: debug). None to log everything.
+        log_level: Option<u8>,
+        /// Contract id of the anchor where the data are read. None to use the target contract.
+        read_contract_id: Option<ContractId>,
+        /// RPC endpoint of the anchor where the data are read. None to use the rpc of the target contract.
+        read_rpc: Option<String>,
+        /// Version of the reply format, added as first byte of the reply.
+        /// None for the implicit version (no version byte).
+        reply_format_version: Option<u8>,
+        /// When true, the winners are sorted by account so the reply doesn't depend on the js order
+        sort_winners: bool,
+    }
+
+    impl ConfigExt {
+        /// Returns the verbosity of the logs
+        fn log_level(&self) -> u8 {
+            self.log_level.unwrap_or(LOG_LEVEL_DEBUG)
+        }
+
+        /// Returns the SS58 prefix used to encode the addresses
+        fn ss58_prefix(&self) -> u16 {
+            self.ss58_prefix.unwrap_or_else(default_ss58_prefix)
+        }
+
+        /// Returns the SS58 prefix expected for the addresses returned by the js, in strict mode
+        fn expected_ss58_prefix(&self) -> Option<u16> {
+            if self.strict_ss58 {
+                Some(self.ss58_prefix())
+            } else {
+                None
+            }
+        }
+    }
+
+    #[derive(Encode, Decode, Debug, Default, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum HashAlgo {
+        #[default]
+        Sha2x256,
+        Blake2x256,
+    }
+
+    /// The settings are decoded in the order of the fields.
+    /// A value stored by a previous version is shorter: the missing settings get their default value.
+    /// So a new setting must be added at the end, in the struct and here.
+    impl Decode for ConfigExt {
+        fn decode<I: scale::Input>(input: &mut I) -> core::result::Result<Self, scale::Error> {
+            Ok(Self {
+                max_rewards: decode_or_default(input)?,
+                graph_api_url: decode_or_default(input)?,
+                enforce_registry: decode_or_default(input)?,
+                max_js_error_len: decode_or_default(input)?,
+                strict_bytes_output: decode_or_default(input)?,
+                fallback_rpc: decode_or_default(input)?,
+                allow_eval_js: decode_or_default(input)?,
+                hash_algo: decode_or_default(input)?,
+                require_rewards: decode_or_default(input)?,
+                max_js_input_len: decode_or_default(input)?,
+                ss58_prefix: decode_or_default(input)?,
+                strict_ss58: decode_or_default(input)?,
+                read_participants_from_anchor: decode_or_default(input)?,
+                max_js_output_bytes: decode_or_default(input)?,
+                include_rewards_in_request: decode_or_default(input)?,
+                restrict_triggers: decode_or_default(input)?,
+                token_decimals: decode_or_default(input)?,
+                default_nb_winners: decode_or_default(input)?,
+                log_level: decode_or_default(input)?,
+                read_contract_id: decode_or_default(input)?,
+                read_rpc: decode_or_default(input)?,
+                reply_format_version: decode_or_default(input)?,
+                sort_winners: decode_or_default(input)?,
+            })
+        }
+    }
+
+    /// Decodes the value, or returns the default value if the input is exhausted
+    fn decode_or_default<T: Decode + Default, I: scale::Input>(
+        input: &mut I,
+    ) -> core::result::Result<T, scale::Error> {
+        if input.remaining_len()? == Some(0) {
+            return Ok(T::default());
+        }
+        T::decode(input)
+    }
+
+    /// Category of the error raised when the js is evaluated
+    #[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum JsErrorKind {
+        /// The script cannot be parsed
+        Syntax,
+        /// The script raised an error during the evaluation
+        Runtime,
+        /// The evaluation is too long
+        Timeout,
+        /// The output of the script is not valid
+        BadOutput,
+        /// The script and its arguments exceed the maximum length of the input
+        InputTooLarge,
+    }
+
+    /// How the transactions are signed and sent to the rollup anchor
+    #[derive(Encode, Decode, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum SigningModel {
+        /// No sender key: the attestor signs and sends the transactions
+        AttestorOnly,
+        /// The attestor signs the meta-transactions and the sender sends them
+        MetaTx { sender: Vec<u8> },
+    }
+
+    #[derive(Encode, Decode, Debug, PartialEq, Eq)]
     #[repr(u8)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub enum ContractError {
@@ -99,21 +277,38 @@ mod lucky_raffle {
         FailedToCreateClient,
         FailedToCommitTx,
         FailedToCallRollup,
-        JsError(String),
+        JsError(JsErrorKind, String),
         FailedToDecode,
         NbWinnersNotSet,
         NextEraUnknown,
+        RewardsOutOfRange,
+        FailedToFetchRegistry,
+        CoreHashMismatch,
+        EvalJsNotAllowed,
+        RewardsNotSet,
+        NbWinnersOutOfRange,
+        WrongSs58Version,
+        KeysMustDiffer,
+        NextEraDecodeFailed,
+        EmptyCoreJs,
+        ExcludedDecodeFailed,
+        CodeHashUnknown,
+        StakeOverflow,
+        ErrorAlreadyReplied,
     }
 
     type Result<T> = core::result::Result<T, ContractError>;
 
     impl From<phat_offchain_rollup::Error> for ContractError {
-        fn from(error: phat_offchain_rollup::Error) -> Self {
-            error!("error in the rollup: {:?}", error);
+        fn from(_error: phat_offchain_rollup::Error) -> Self {
+            // the error is logged by the caller, with the configured log level
             ContractError::FailedToCallRollup
         }
     }
 
+    /// Version of the contract (major, minor, patch)
+    const VERSION: (u16, u16, u16) = (2, 0, 0);
+
     impl JsOffchainRollup {
         #[ink(constructor)]
         pub fn default() -> Self {
@@ -125,6 +320,10 @@ mod lucky_raffle {
                 attest_key: private_key[..32].try_into().expect("Invalid Key Length"),
                 config: None,
                 core_js: Default::default(),
+                pending_owner: Default::default(),
+                config_ext: Default::default(),
+                blocklist: Default::default(),
+                triggers: Default::default(),
             }
         }
 
@@ -134,6 +333,21 @@ mod lucky_raffle {
             self.owner
         }
 
+        /// Gets the version of the contract (major, minor, patch)
+        #[ink(message)]
+        pub fn version(&self) -> (u16, u16, u16) {
+            VERSION
+        }
+
+        /// Gets the code hash of the contract
+        #[ink(message)]
+        pub fn code_hash_self(&self) -> Result<Hash> {
+            self.env()
+                .own_code_hash()
+                .log_err_at(self.get_log_level(), "failed to get the code hash")
+                .or(Err(ContractError::CodeHashUnknown))
+        }
+
         /// Gets the attestor address used by this rollup
         #[ink(message)]
         pub fn get_attest_address(&self) -> Vec<u8> {
@@ -150,6 +364,12 @@ mod lucky_raffle {
             output.to_vec()
         }
 
+        /// Returns true if the expected address is the ecdsa address used by this rollup
+        #[ink(message)]
+        pub fn verify_ecdsa_address(&self, expected: Vec<u8>) -> bool {
+            self.get_attest_ecdsa_address() == expected
+        }
+
         /// Gets the sender address used by this rollup (in case of meta-transaction)
         #[ink(message)]
         pub fn get_sender_address(&self) -> Option<Vec<u8>> {
@@ -161,6 +381,23 @@ mod lucky_raffle {
             }
         }
 
+        /// Gets the SS58 address of the sender (in case of meta-transaction), with the configured prefix
+        #[ink(message)]
+        pub fn get_sender_ss58(&self) -> Option<String> {
+            let sender: [u8; 32] = self.get_sender_address()?.try_into().ok()?;
+            let ss58_prefix = self.get_ss58_config().0;
+            Some(convert_address_input(&AccountId::from(sender), ss58_prefix))
+        }
+
+        /// Gets how the transactions are signed and sent to the rollup anchor
+        #[ink(message)]
+        pub fn get_signing_model(&self) -> SigningModel {
+            match self.get_sender_address() {
+                Some(sender) => SigningModel::MetaTx { sender },
+                None => SigningModel::AttestorOnly,
+            }
+        }
+
         /// Gets the config of the target consumer contract
         #[ink(message)]
         pub fn get_target_contract(&self) -> Option<(String, u8, u8, ContractId)> {
@@ -169,6 +406,30 @@ mod lucky_raffle {
                 .map(|c| (c.rpc.clone(), c.pallet_id, c.call_id, c.contract_id))
         }
 
+        /// Gets the RPC endpoint of the target consumer contract
+        #[ink(message)]
+        pub fn get_rpc(&self) -> Option<String> {
+            self.config.as_ref().map(|c| c.rpc.clone())
+        }
+
+        /// Gets the pallet id of the target consumer contract
+        #[ink(message)]
+        pub fn get_pallet_id(&self) -> Option<u8> {
+            self.config.as_ref().map(|c| c.pallet_id)
+        }
+
+        /// Gets the call id of the target consumer contract
+        #[ink(message)]
+        pub fn get_call_id(&self) -> Option<u8> {
+            self.config.as_ref().map(|c| c.call_id)
+        }
+
+        /// Gets the address of the target consumer contract
+        #[ink(message)]
+        pub fn get_contract_id(&self) -> Option<ContractId> {
+            self.config.as_ref().map(|c| c.contract_id)
+        }
+
         /// Configures the target consumer contract (admin only)
         #[ink(message)]
         pub fn config_target_contract(
@@ -180,458 +441,5204 @@ mod lucky_raffle {
             sender_key: Option<Vec<u8>>,
         ) -> Result<()> {
             self.ensure_owner()?;
+            let contract_id = contract_id
+                .try_into()
+                .or(Err(ContractError::InvalidAddressLength))?;
+            let sender_key = match sender_key {
+                Some(key) => Some(key.try_into().or(Err(ContractError::InvalidKeyLength))?),
+                None => None,
+            };
+            if let Some(sender_key) = sender_key.as_ref() {
+                self.ensure_differs_from_attest_key(sender_key)?;
+            }
+            // the other settings (config_ext) are kept when the target contract is reconfigured
             self.config = Some(Config {
                 rpc,
                 pallet_id,
                 call_id,
-                contract_id: contract_id
-                    .try_into()
-                    .or(Err(ContractError::InvalidAddressLength))?,
-                sender_key: match sender_key {
-                    Some(key) => Some(key.try_into().or(Err(ContractError::InvalidKeyLength))?),
-                    None => None,
-                },
+                contract_id,
+                sender_key,
             });
             Ok(())
         }
 
-        /// Get the core script
+        /// Returns Error if the sender key is the same as the attest key
+        fn ensure_differs_from_attest_key(&self, sender_key: &[u8; 32]) -> Result<()> {
+            if *sender_key == self.attest_key {
+                self.log_error(format_args!(
+                    "the sender key must differ from the attest key"
+                ));
+                return Err(ContractError::KeysMustDiffer);
+            }
+            Ok(())
+        }
+
+        /// Gets the maximum rewards accepted in the output of the js
         #[ink(message)]
-        pub fn get_core_js(&self) -> Option<CoreJs> {
-            self.core_js.get()
+        pub fn get_max_rewards(&self) -> Option<Balance> {
+            self.config_ext().max_rewards
         }
 
-        /// Configures the core js (script + settings) (admin only)
+        /// Sets the maximum rewards accepted in the output of the js. None for no limit (admin only)
         #[ink(message)]
-        pub fn config_core_js(&mut self, script: String, settings: String) -> Result<()> {
+        pub fn set_max_rewards(&mut self, max_rewards: Option<Balance>) -> Result<()> {
             self.ensure_owner()?;
-            self.config_core_js_inner(script, settings);
+            self.update_config_ext(|c| c.max_rewards = max_rewards)?;
             Ok(())
         }
 
-        /// Configures the core js (only script) (admin only)
+        /// Rotates the key used to send the meta-transaction and returns the new sender address (admin only)
         #[ink(message)]
-        pub fn config_core_js_script(&mut self, script: String) -> Result<()> {
+        pub fn rotate_sender_key(&mut self, new_key: Vec<u8>) -> Result<Vec<u8>> {
             self.ensure_owner()?;
-            let Some(CoreJs { settings, .. }) = self.core_js.get() else {
-                error!("CoreNotConfigured");
-                return Err(ContractError::CoreNotConfigured);
-            };
-            self.config_core_js_inner(script, settings);
-            Ok(())
+            let sender_key: [u8; 32] = new_key
+                .try_into()
+                .or(Err(ContractError::InvalidKeyLength))?;
+            self.ensure_differs_from_attest_key(&sender_key)?;
+            self.ensure_client_configured_mut()?.sender_key = Some(sender_key);
+            Ok(signing::get_public_key(
+                &sender_key,
+                signing::SigType::Sr25519,
+            ))
         }
 
-        /// Configures the core js (only script) (admin only)
+        /// Gets the config of the registry publishing the official code hash of the core js
         #[ink(message)]
-        pub fn config_core_js_settings(&mut self, settings: String) -> Result<()> {
+        pub fn get_registry(&self) -> Option<(Option<String>, bool)> {
+            self.config.as_ref()?;
+            let config_ext = self.config_ext();
+            Some((config_ext.graph_api_url, config_ext.enforce_registry))
+        }
+
+        /// Configures the registry publishing the official code hash of the core js (admin only)
+        #[ink(message)]
+        pub fn config_registry(
+            &mut self,
+            graph_api_url: Option<String>,
+            enforce_registry: bool,
+        ) -> Result<()> {
             self.ensure_owner()?;
-            let Some(CoreJs { script, .. }) = self.core_js.get() else {
-                error!("CoreNotConfigured");
+            self.update_config_ext(|c| {
+                c.graph_api_url = graph_api_url;
+                c.enforce_registry = enforce_registry;
+            })?;
+            Ok(())
+        }
+
+        /// Checks if the code hash of the core js matches the one published by the registry
+        #[ink(message)]
+        pub fn verify_core_against_registry(&self) -> Result<bool> {
+            self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let log_level = config_ext.log_level();
+            let Some(graph_api_url) = config_ext.graph_api_url else {
+                log_error(log_level, format_args!("GraphApiNotConfigured"));
+                return Err(ContractError::GraphApiNotConfigured);
+            };
+            let Some(CoreJs { code_hash, .. }) = self.core_js.get() else {
+                log_error(log_level, format_args!("CoreNotConfigured"));
                 return Err(ContractError::CoreNotConfigured);
             };
-            self.config_core_js_inner(script, settings);
-            Ok(())
+
+            let response = pink_extension::http_get!(graph_api_url.as_str());
+            if response.status_code != 200 {
+                log_error(
+                    log_level,
+                    format_args!(
+                        "Fail to read the registry with status code: {}",
+                        response.status_code
+                    ),
+                );
+                return Err(ContractError::FailedToFetchRegistry);
+            }
+            let expected_code_hash = parse_code_hash(&response.body)?;
+            Ok(expected_code_hash == code_hash)
         }
 
-        fn config_core_js_inner(&mut self, script: String, settings: String) {
-            let code_hash = self
-                .env()
-                .hash_bytes::<ink::env::hash::Sha2x256>(script.as_bytes());
-            let settings_hash = self
-                .env()
-                .hash_bytes::<ink::env::hash::Sha2x256>(settings.as_bytes());
-            self.core_js.set(&CoreJs {
-                script,
-                settings,
-                code_hash,
-                settings_hash,
-            });
+        /// Gets the maximum length (in bytes) of the js error returned
+        #[ink(message)]
+        pub fn get_max_js_error_len(&self) -> u32 {
+            self.config_ext()
+                .max_js_error_len
+                .unwrap_or(DEFAULT_MAX_JS_ERROR_LEN)
         }
 
-        /// Transfers the ownership of the contract (admin only)
+        /// Sets the maximum length (in bytes) of the js error returned. None to use the default length (admin only)
         #[ink(message)]
-        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+        pub fn set_max_js_error_len(&mut self, max_js_error_len: Option<u32>) -> Result<()> {
             self.ensure_owner()?;
-            self.owner = new_owner;
+            self.update_config_ext(|c| c.max_js_error_len = max_js_error_len)?;
             Ok(())
         }
 
-        const NEXT_ERA: u32 = ink::selector_id!("NEXT_ERA");
-        const NB_WINNERS: u32 = ink::selector_id!("NB_WINNERS");
-        const LAST_WINNERS: u32 = ink::selector_id!("LAST_WINNER");
-
-        /// Run the raffle
+        /// Gets the verbosity of the logs (0: off, 1: error, 2: info, 3: debug)
         #[ink(message)]
-        pub fn run_raffle(&self) -> Result<Option<Vec<u8>>> {
-            let config = self.ensure_client_configured()?;
-            let mut client = connect(config)?;
+        pub fn get_log_level(&self) -> u8 {
+            self.config_ext().log_level()
+        }
 
-            let era = client
-                .get(&Self::NEXT_ERA)
-                .log_err("run raffle: next era unknown")?
-                .ok_or(ContractError::NextEraUnknown)?;
+        /// Sets the verbosity of the logs (0: off, 1: error, 2: info, 3: debug).
+        /// None to log everything (admin only)
+        #[ink(message)]
+        pub fn set_log_level(&mut self, log_level: Option<u8>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.log_level = log_level)?;
+            Ok(())
+        }
 
-            let nb_winners = client
-                .get(&Self::NB_WINNERS)
-                .log_err("run raffle: nb winners not set")?
-                .ok_or(ContractError::NbWinnersNotSet)?;
+        /// Logs an error if the log level allows it
+        fn log_error(&self, args: core::fmt::Arguments) {
+            log_error(self.get_log_level(), args);
+        }
 
-            let excluded: Vec<AccountId> = client
-                .get(&Self::LAST_WINNERS)
-                .log_err("run raffle: error when getting excluded addresses")?
-                .unwrap_or_default();
+        /// Logs an info if the log level allows it
+        fn log_info(&self, args: core::fmt::Arguments) {
+            log_info(self.get_log_level(), args);
+        }
 
-            let request = RequestSc {
-                era,
-                nb_winners,
-                excluded,
-            };
-            let response = self.handle_request(&request)?;
-            // Attach an action to the tx by:
-            client.action(Action::Reply(response.encode()));
+        /// Logs a debug message if the log level allows it
+        fn log_debug(&self, args: core::fmt::Arguments) {
+            log_debug(self.get_log_level(), args);
+        }
 
-            maybe_submit_tx(client, &self.attest_key, config.sender_key.as_ref())
+        /// Gets the anchor where the data are read (rpc, contract id), if it differs from the target contract
+        #[ink(message)]
+        pub fn get_read_anchor(&self) -> Option<(String, ContractId)> {
+            let config = self.config.as_ref()?;
+            read_anchor(config, &self.config_ext())
+                .map(|(rpc, contract_id)| (rpc.to_string(), contract_id))
         }
 
-        /// Processes a request with the core js and returns the response.
-        fn handle_request(&self, request_sc: &RequestSc) -> Result<ResponseMessage> {
-            let Some(CoreJs {
-                script,
-                code_hash,
-                settings,
-                settings_hash,
-            }) = self.core_js.get()
-            else {
-                error!("CoreNotConfigured");
-                return Err(ContractError::CoreNotConfigured);
+        /// Configures the anchor where the data are read (NEXT_ERA, NB_WINNERS, LAST_WINNERS, ...).
+        /// The reply is still sent to the target contract.
+        /// None to read the data from the target contract (admin only)
+        #[ink(message)]
+        pub fn config_read_anchor(
+            &mut self,
+            read_contract_id: Option<Vec<u8>>,
+            read_rpc: Option<String>,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            let read_contract_id = match read_contract_id {
+                Some(id) => Some(id.try_into().or(Err(ContractError::InvalidAddressLength))?),
+                None => None,
             };
+            self.update_config_ext(|c| {
+                c.read_contract_id = read_contract_id;
+                c.read_rpc = read_rpc;
+            })?;
+            Ok(())
+        }
 
-            let request_js = convert_request(request_sc);
-            let output_value_js = self.run_js_inner(&script, &request_js.encode(), settings)?;
+        /// Returns true if the winners are sorted by account in the reply
+        #[ink(message)]
+        pub fn get_sort_winners(&self) -> bool {
+            self.config_ext().sort_winners
+        }
 
-            let input_hash = self
-                .env()
-                .hash_bytes::<ink::env::hash::Sha2x256>(&request_sc.encode());
-            let response = ResponseMessage::JsResponse {
-                js_script_hash: code_hash,
-                input_hash,
-                settings_hash,
-                output_value: convert_output(output_value_js),
-            };
+        /// Sets if the winners are sorted by account in the reply, instead of the js order (admin only)
+        #[ink(message)]
+        pub fn set_sort_winners(&mut self, sort_winners: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.sort_winners = sort_winners)?;
+            Ok(())
+        }
 
-            Ok(response)
+        /// Gets the number of winners used when it is not set in the rollup anchor
+        #[ink(message)]
+        pub fn get_default_nb_winners(&self) -> Option<u16> {
+            self.config_ext().default_nb_winners
         }
 
-        /// Processes a request with the core js and returns the output.
-        fn run_js_inner(&self, js_code: &str, request: &[u8], settings: String) -> Result<Vec<u8>> {
-            let args = alloc::vec![alloc::format!("0x{}", hex_fmt::HexFmt(request)), settings];
+        /// Sets the number of winners used when it is not set in the rollup anchor.
+        /// None to fail when it is not set (admin only)
+        #[ink(message)]
+        pub fn set_default_nb_winners(&mut self, default_nb_winners: Option<u16>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.default_nb_winners = default_nb_winners)?;
+            Ok(())
+        }
 
-            let output = phat_js::eval(js_code, &args)
-                .log_err("Failed to eval the core js")
-                .map_err(ContractError::JsError)?;
+        /// Gets the number of decimals of the token used to display the rewards
+        #[ink(message)]
+        pub fn get_token_decimals(&self) -> u8 {
+            self.config_ext()
+                .token_decimals
+                .unwrap_or(DEFAULT_TOKEN_DECIMALS)
+        }
 
-            let output_as_bytes = match output {
-                phat_js::Output::String(s) => s.into_bytes(),
-                phat_js::Output::Bytes(b) => b,
-                phat_js::Output::Undefined => {
-                    return Err(ContractError::JsError("Undefined output".to_string()))
-                }
-            };
+        /// Sets the number of decimals of the token. None to use the default decimals (admin only)
+        #[ink(message)]
+        pub fn set_token_decimals(&mut self, token_decimals: Option<u8>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.token_decimals = token_decimals)?;
+            Ok(())
+        }
 
-            Ok(output_as_bytes)
+        /// Formats the rewards as a decimal string with the number of decimals of the token
+        #[ink(message)]
+        pub fn format_rewards(&self, value: Balance) -> String {
+            format_balance(value, self.get_token_decimals())
         }
-        /// Simulate the js
-        ///
-        /// For dev purpose. (admin only)
+
+        /// Returns true if the js must return bytes
         #[ink(message)]
-        pub fn dry_run_with_parameters(
-            &self,
-            era: u32,
-            nb_winners: u16,
-            excluded: Vec<AccountId>,
-        ) -> Result<Vec<u8>> {
-            self.ensure_owner()?;
-            self.ensure_client_configured()?;
-            let request = RequestSc {
-                era,
-                nb_winners,
-                excluded,
-            };
-            let response = self.handle_request(&request)?;
-            let encoded_response = response.encode();
-            info!("encoded response : {:02x?}", encoded_response);
-            Ok(encoded_response)
+        pub fn get_strict_bytes_output(&self) -> bool {
+            self.config_ext().strict_bytes_output
         }
 
-        /// Simulate the js
-        ///
-        /// For dev purpose. (admin only)
+        /// Sets if the js must return bytes (a string output is rejected) (admin only)
         #[ink(message)]
-        pub fn dry_run(&self) -> Result<Vec<u8>> {
+        pub fn set_strict_bytes_output(&mut self, strict_bytes_output: bool) -> Result<()> {
             self.ensure_owner()?;
+            self.update_config_ext(|c| c.strict_bytes_output = strict_bytes_output)?;
+            Ok(())
+        }
 
-            let config = self.ensure_client_configured()?;
-            let mut client = connect(config)?;
-
-            let era = client
-                .get(&Self::NEXT_ERA)
-                .log_err("run raffle: next era unknown")?
-                .ok_or(ContractError::NextEraUnknown)?;
+        /// Gets the RPC endpoint used when the primary one fails
+        #[ink(message)]
+        pub fn get_fallback_rpc(&self) -> Option<String> {
+            self.config_ext().fallback_rpc
+        }
 
-            let nb_winners = client
-                .get(&Self::NB_WINNERS)
-                .log_err("run raffle: nb winners not set")?
-                .ok_or(ContractError::NbWinnersNotSet)?;
-            info!("nb_winners : {:?}", nb_winners);
-
-            let excluded: Vec<AccountId> = client
-                .get(&Self::LAST_WINNERS)
-                .log_err("run raffle: error when getting excluded addresses")?
-                .unwrap_or_default();
-            info!("excluded : {:?}", excluded);
+        /// Sets the RPC endpoint used when the primary one fails (admin only)
+        #[ink(message)]
+        pub fn set_fallback_rpc(&mut self, fallback_rpc: Option<String>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.fallback_rpc = fallback_rpc)?;
+            Ok(())
+        }
 
-            self.dry_run_with_parameters(era, nb_winners, excluded)
+        /// Returns true if the raffle fails when the js doesn't provide any rewards
+        #[ink(message)]
+        pub fn get_require_rewards(&self) -> bool {
+            self.config_ext().require_rewards
         }
 
-        /// Returns BadOrigin error if the caller is not the owner
-        fn ensure_owner(&self) -> Result<()> {
-            if self.env().caller() == self.owner {
-                Ok(())
-            } else {
-                Err(ContractError::BadOrigin)
-            }
+        /// Sets if the raffle fails when the js doesn't provide any rewards (admin only)
+        #[ink(message)]
+        pub fn set_require_rewards(&mut self, require_rewards: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.require_rewards = require_rewards)?;
+            Ok(())
         }
 
-        /// Returns the config reference or raise the error `ClientNotConfigured`
-        fn ensure_client_configured(&self) -> Result<&Config> {
-            self.config
-                .as_ref()
-                .ok_or(ContractError::ClientNotConfigured)
+        /// Returns true if the participants are read from the rollup anchor and given to the js
+        #[ink(message)]
+        pub fn get_read_participants_from_anchor(&self) -> bool {
+            self.config_ext().read_participants_from_anchor
         }
-    }
 
-    fn connect(config: &Config) -> Result<InkRollupClient> {
-        let result = InkRollupClient::new(
-            &config.rpc,
-            config.pallet_id,
-            config.call_id,
-            &config.contract_id,
-        )
-        .log_err("failed to create rollup client");
+        /// Sets if the participants are read from the rollup anchor and given to the js (admin only)
+        #[ink(message)]
+        pub fn set_read_participants_from_anchor(
+            &mut self,
+            read_participants_from_anchor: bool,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| {
+                c.read_participants_from_anchor = read_participants_from_anchor
+            })?;
+            Ok(())
+        }
 
-        match result {
-            Ok(client) => Ok(client),
-            Err(e) => {
-                error!("Error : {:?}", e);
-                Err(ContractError::FailedToCreateClient)
-            }
+        /// Returns true if the rewards are read from the rollup anchor and given to the js
+        #[ink(message)]
+        pub fn get_include_rewards_in_request(&self) -> bool {
+            self.config_ext().include_rewards_in_request
         }
-    }
 
-    fn maybe_submit_tx(
-        client: InkRollupClient,
+        /// Sets if the rewards are read from the rollup anchor and given to the js (admin only)
+        #[ink(message)]
+        pub fn set_include_rewards_in_request(
+            &mut self,
+            include_rewards_in_request: bool,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.include_rewards_in_request = include_rewards_in_request)?;
+            Ok(())
+        }
+
+        /// Gets the maximum length (in bytes) of the script and the arguments given to the js
+        #[ink(message)]
+        pub fn get_max_js_input_len(&self) -> Option<u32> {
+            self.config_ext().max_js_input_len
+        }
+
+        /// Sets the maximum length (in bytes) of the script and the arguments given to the js.
+        /// None for no limit (admin only)
+        #[ink(message)]
+        pub fn set_max_js_input_len(&mut self, max_js_input_len: Option<u32>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.max_js_input_len = max_js_input_len)?;
+            Ok(())
+        }
+
+        /// Gets the maximum length (in bytes) of the output of the js
+        #[ink(message)]
+        pub fn get_max_js_output_bytes(&self) -> Option<u32> {
+            self.config_ext().max_js_output_bytes
+        }
+
+        /// Sets the maximum length (in bytes) of the output of the js. None for no limit (admin only)
+        #[ink(message)]
+        pub fn set_max_js_output_bytes(&mut self, max_js_output_bytes: Option<u32>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.max_js_output_bytes = max_js_output_bytes)?;
+            Ok(())
+        }
+
+        /// Gets the SS58 prefix used to encode the addresses and if the strict mode is enabled
+        #[ink(message)]
+        pub fn get_ss58_config(&self) -> (u16, bool) {
+            let config_ext = self.config_ext();
+            (config_ext.ss58_prefix(), config_ext.strict_ss58)
+        }
+
+        /// Gets the settings used to display the addresses and the amounts: (ss58 prefix, token decimals)
+        #[ink(message)]
+        pub fn get_display_settings(&self) -> (u16, u8) {
+            (self.get_ss58_config().0, self.get_token_decimals())
+        }
+
+        /// Encodes the public key as SS58 address with each given prefix
+        #[ink(message)]
+        pub fn encode_ss58_multi(
+            &self,
+            pubkey: [u8; 32],
+            prefixes: Vec<u16>,
+        ) -> Vec<(u16, String)> {
+            let address = AccountId::from(pubkey);
+            prefixes
+                .into_iter()
+                .map(|prefix| (prefix, convert_address_input(&address, prefix)))
+                .collect()
+        }
+
+        /// Decodes the SS58 address, only if it is encoded with one of the allowed prefixes
+        #[ink(message)]
+        pub fn decode_ss58_checked(
+            &self,
+            address: String,
+            allowed_prefixes: Vec<u16>,
+        ) -> Result<AccountId> {
+            let log_level = self.get_log_level();
+            let (account_id, version) = AccountId32::from_ss58check_with_version(&address)
+                .log_err_at(log_level, "incorrect address")
+                .or(Err(ContractError::FailedToDecode))?;
+            if !allowed_prefixes.contains(&version.prefix()) {
+                log_error(
+                    log_level,
+                    format_args!(
+                        "address {address} encoded with the prefix {} not allowed",
+                        version.prefix()
+                    ),
+                );
+                return Err(ContractError::WrongSs58Version);
+            }
+            let address_hex: [u8; 32] = scale::Encode::encode(&account_id)
+                .try_into()
+                .or(Err(ContractError::InvalidAddressLength))?;
+            Ok(AccountId::from(address_hex))
+        }
+
+        /// Checks if the addresses can be decoded with the SS58 config of the contract
+        #[ink(message)]
+        pub fn validate_ss58_batch(&self, addresses: Vec<String>) -> Vec<(String, bool)> {
+            let config_ext = self.config_ext();
+            let expected_prefix = config_ext.expected_ss58_prefix();
+            let log_level = config_ext.log_level();
+            addresses
+                .into_iter()
+                .map(|address| {
+                    let valid =
+                        convert_address_output(&address, expected_prefix, log_level).is_ok();
+                    (address, valid)
+                })
+                .collect()
+        }
+
+        /// Configures the SS58 prefix used to encode the addresses (None for Astar prefix),
+        /// and if the addresses returned by the js must be encoded with this prefix (admin only)
+        #[ink(message)]
+        pub fn config_ss58(&mut self, ss58_prefix: Option<u16>, strict_ss58: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| {
+                c.ss58_prefix = ss58_prefix;
+                c.strict_ss58 = strict_ss58;
+            })?;
+            Ok(())
+        }
+
+        /// Get the core script
+        #[ink(message)]
+        pub fn get_core_js(&self) -> Option<CoreJs> {
+            self.core_js.get()
+        }
+
+        /// Gets the hashes of the core script and its settings: (code_hash, settings_hash)
+        #[ink(message)]
+        pub fn get_core_js_hashes(&self) -> Option<(CodeHash, CodeHash)> {
+            let CoreJs {
+                code_hash,
+                settings_hash,
+                ..
+            } = self.core_js.get()?;
+            Some((code_hash, settings_hash))
+        }
+
+        /// Gets the value of a field in the settings of the core js, when the settings are a flat json object of strings
+        #[ink(message)]
+        pub fn get_core_js_setting(&self, key: String) -> Option<String> {
+            let CoreJs { settings, .. } = self.core_js.get()?;
+            let CoreJsSettings(mut fields) = pink_json::from_str(&settings).ok()?;
+            fields.remove(&key)
+        }
+
+        /// Configures the core js (script + settings) (admin only)
+        #[ink(message)]
+        pub fn config_core_js(&mut self, script: String, settings: String) -> Result<()> {
+            self.ensure_owner()?;
+            self.config_core_js_inner(script, settings)
+        }
+
+        /// Configures the core js (only script) (admin only)
+        #[ink(message)]
+        pub fn config_core_js_script(&mut self, script: String) -> Result<()> {
+            self.ensure_owner()?;
+            let Some(CoreJs { settings, .. }) = self.core_js.get() else {
+                self.log_error(format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+            self.config_core_js_inner(script, settings)
+        }
+
+        /// Configures the core js (only script) (admin only)
+        #[ink(message)]
+        pub fn config_core_js_settings(&mut self, settings: String) -> Result<()> {
+            self.ensure_owner()?;
+            let Some(CoreJs { script, .. }) = self.core_js.get() else {
+                self.log_error(format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+            self.config_core_js_inner(script, settings)
+        }
+
+        fn config_core_js_inner(&mut self, script: String, settings: String) -> Result<()> {
+            if script.trim().is_empty() {
+                self.log_error(format_args!("EmptyCoreJs"));
+                return Err(ContractError::EmptyCoreJs);
+            }
+            let code_hash = self.hash_bytes(script.as_bytes());
+            let settings_hash = self.hash_bytes(settings.as_bytes());
+            self.core_js.set(&CoreJs {
+                script,
+                settings,
+                code_hash,
+                settings_hash,
+            });
+            self.env().emit_event(CoreJsConfigured {
+                code_hash,
+                settings_hash,
+            });
+            Ok(())
+        }
+
+        /// Hashes the input with the algorithm configured
+        fn hash_bytes(&self, input: &[u8]) -> CodeHash {
+            let hash_algo = self.config_ext().hash_algo;
+            match hash_algo {
+                HashAlgo::Sha2x256 => self.env().hash_bytes::<ink::env::hash::Sha2x256>(input),
+                HashAlgo::Blake2x256 => self.env().hash_bytes::<ink::env::hash::Blake2x256>(input),
+            }
+        }
+
+        /// Hashes the request, independently of the order of the excluded addresses and participants
+        fn compute_input_hash(&self, request_sc: &RequestSc) -> CodeHash {
+            self.hash_bytes(&sort_request(request_sc).encode())
+        }
+
+        /// Gets the algorithm used to hash the core js and the request
+        #[ink(message)]
+        pub fn get_hash_algo(&self) -> HashAlgo {
+            self.config_ext().hash_algo
+        }
+
+        /// Sets the algorithm used to hash the core js and the request (admin only)
+        /// The hashes of the core js are computed again with the new algorithm.
+        #[ink(message)]
+        pub fn set_hash_algo(&mut self, hash_algo: HashAlgo) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.hash_algo = hash_algo)?;
+            if let Some(CoreJs {
+                script, settings, ..
+            }) = self.core_js.get()
+            {
+                self.config_core_js_inner(script, settings)?;
+            }
+            Ok(())
+        }
+
+        /// Gets the pending owner, waiting to accept the ownership
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner.get().flatten()
+        }
+
+        /// Proposes a new owner. The ownership is transferred when the new owner accepts it (admin only)
+        #[ink(message)]
+        pub fn propose_owner(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.pending_owner.set(&Some(new_owner));
+            Ok(())
+        }
+
+        /// Accepts the ownership of the contract (pending owner only)
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_owner() != Some(caller) {
+                return Err(ContractError::BadOrigin);
+            }
+            self.set_owner(caller);
+            Ok(())
+        }
+
+        /// Cancels the pending transfer of ownership (admin only)
+        #[ink(message)]
+        pub fn cancel_ownership_transfer(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.pending_owner.set(&None);
+            Ok(())
+        }
+
+        /// Changes the owner and drops the pending transfer of ownership
+        fn set_owner(&mut self, new_owner: AccountId) {
+            self.owner = new_owner;
+            self.pending_owner.set(&None);
+        }
+
+        const NEXT_ERA: u32 = ink::selector_id!("NEXT_ERA");
+        const NB_WINNERS: u32 = ink::selector_id!("NB_WINNERS");
+        const LAST_WINNERS: u32 = ink::selector_id!("LAST_WINNER");
+        const PARTICIPANTS: u32 = ink::selector_id!("PARTICIPANTS");
+        const REWARDS: u32 = ink::selector_id!("REWARDS");
+        const MIN_PARTICIPANTS: u32 = ink::selector_id!("MIN_PARTICIPANTS");
+
+        /// Checks if the contract is fully configured to run the raffle.
+        /// Returns the list of the missing configurations.
+        #[ink(message)]
+        pub fn is_ready(&self) -> (bool, Vec<ContractError>) {
+            let mut errors = Vec::new();
+            if self.config.is_none() {
+                errors.push(ContractError::ClientNotConfigured);
+            } else {
+                let config_ext = self.config_ext();
+                if config_ext.enforce_registry && config_ext.graph_api_url.is_none() {
+                    errors.push(ContractError::GraphApiNotConfigured);
+                }
+            }
+            if self.core_js.get().is_none() {
+                errors.push(ContractError::CoreNotConfigured);
+            }
+            (errors.is_empty(), errors)
+        }
+
+        /// Gets the selectors of the keys read in the rollup anchor (NEXT_ERA, NB_WINNERS, LAST_WINNERS)
+        #[ink(message)]
+        pub fn get_rollup_key_selectors(&self) -> (u32, u32, u32) {
+            (Self::NEXT_ERA, Self::NB_WINNERS, Self::LAST_WINNERS)
+        }
+
+        /// Gets the value expected in the rollup anchor for the NEXT_ERA key
+        #[ink(message)]
+        pub fn encode_next_era_value(&self, era: u32) -> Vec<u8> {
+            era.encode()
+        }
+
+        /// Checks the worker can reach the rollup anchor with the configured rpc (health check)
+        #[ink(message)]
+        pub fn ping_rpc(&self) -> Result<bool> {
+            let config = self.ensure_client_configured()?;
+            let log_level = self.get_log_level();
+            let mut client = connect_rpc(config, &config.rpc, log_level)?;
+            ping_client(&mut client, log_level)
+        }
+
+        /// Gets the number of winners configured in the rollup anchor
+        #[ink(message)]
+        pub fn get_nb_winners(&self) -> Result<Option<u16>> {
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let log_level = config_ext.log_level();
+            let mut client = connect_read_anchor(config, &config_ext)?;
+            read_nb_winners(&mut client, log_level)
+        }
+
+        /// Run the raffle
+        #[ink(message)]
+        pub fn run_raffle(&self) -> Result<Option<Vec<u8>>> {
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            self.ensure_trigger_allowed(&config_ext)?;
+            let log_level = config_ext.log_level();
+            if config_ext.enforce_registry && !self.verify_core_against_registry()? {
+                log_error(
+                    log_level,
+                    format_args!("run raffle: the core js doesn't match the registry"),
+                );
+                return Err(ContractError::CoreHashMismatch);
+            }
+
+            let connect_target = |rpc: &str| connect_rpc(config, rpc, log_level);
+            match read_anchor(config, &config_ext) {
+                // the data can be read from another anchor than the one receiving the reply
+                Some(_) => {
+                    let mut reader = connect_read_anchor(config, &config_ext)?;
+                    self.run_raffle_with_reader(config, &config_ext, &mut reader, connect_target)
+                }
+                None => self.run_raffle_with_client(config, &config_ext, connect_target),
+            }
+        }
+
+        /// Reads the data and sends the reply with the same client, so the commit is conditioned by the reads.
+        /// The fallback rpc is used only if the primary rpc fails: the data are read again with the new client
+        /// and the reply is computed again only if the data changed.
+        fn run_raffle_with_client<C>(
+            &self,
+            config: &Config,
+            config_ext: &ConfigExt,
+            mut connect_client: impl FnMut(&str) -> Result<C>,
+        ) -> Result<Option<Vec<u8>>>
+        where
+            C: RollupReader + RollupWriter,
+            ContractError: From<C::Error>,
+        {
+            let mut computed: Option<((RequestSc, u32), ResponseMessage)> = None;
+            with_fallback_rpc(config, config_ext, |rpc| {
+                let mut client = connect_client(rpc)?;
+                let inputs = self.read_raffle_inputs(config_ext, &mut client)?;
+                let response = match computed.take() {
+                    Some((previous_inputs, response)) if previous_inputs == inputs => response,
+                    _ => {
+                        let (request, min_participants) = &inputs;
+                        self.build_reply(config_ext, request, *min_participants)?
+                    }
+                };
+                let result = self.submit_reply(config, config_ext, client, inputs.0.era, &response);
+                computed = Some((inputs, response));
+                result
+            })
+        }
+
+        /// Reads the data with the reader and sends the reply with a writer connected to the target contract.
+        /// The reply is computed once, only the commit is retried with the fallback rpc.
+        fn run_raffle_with_reader<R, W>(
+            &self,
+            config: &Config,
+            config_ext: &ConfigExt,
+            reader: &mut R,
+            connect_writer: impl Fn(&str) -> Result<W>,
+        ) -> Result<Option<Vec<u8>>>
+        where
+            R: RollupReader,
+            W: RollupWriter,
+            ContractError: From<R::Error>,
+        {
+            let (era, response) = self.compute_reply(config_ext, reader)?;
+            with_fallback_rpc(config, config_ext, |rpc| {
+                self.submit_reply(config, config_ext, connect_writer(rpc)?, era, &response)
+            })
+        }
+
+        /// Attaches the reply to the tx and submits it to the rollup anchor.
+        /// An error is replied once per era and per cooldown, because the anchor doesn't advance the era
+        /// when it receives an error.
+        fn submit_reply(
+            &self,
+            config: &Config,
+            config_ext: &ConfigExt,
+            writer: impl RollupWriter,
+            era: u32,
+            response: &ResponseMessage,
+        ) -> Result<Option<Vec<u8>>> {
+            let log_level = config_ext.log_level();
+            let is_error = matches!(response, ResponseMessage::Error { .. });
+            if is_error && error_replied_for_era(era) {
+                log_info(
+                    log_level,
+                    format_args!("an error was already replied for era {era}"),
+                );
+                return Err(ContractError::ErrorAlreadyReplied);
+            }
+            let result = writer.commit_reply(
+                encode_reply(config_ext, response),
+                &self.attest_key,
+                config.sender_key.as_ref(),
+                log_level,
+            )?;
+            if is_error {
+                save_error_replied(era, log_level);
+            }
+            Ok(result)
+        }
+
+        /// Reads the request from the rollup anchor and computes the reply
+        fn compute_reply<R>(
+            &self,
+            config_ext: &ConfigExt,
+            client: &mut R,
+        ) -> Result<(u32, ResponseMessage)>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let (request, min_participants) = self.read_raffle_inputs(config_ext, client)?;
+            let response = self.build_reply(config_ext, &request, min_participants)?;
+            Ok((request.era, response))
+        }
+
+        /// Reads the request and the minimum of participants from the rollup anchor
+        fn read_raffle_inputs<R>(
+            &self,
+            config_ext: &ConfigExt,
+            client: &mut R,
+        ) -> Result<(RequestSc, u32)>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let request = self.read_request(config_ext, client)?;
+            let min_participants = self.read_min_participants(config_ext, client)?;
+            Ok((request, min_participants))
+        }
+
+        /// Reads the minimum of participants required to run the raffle, 0 if it is not set
+        fn read_min_participants<R>(&self, config_ext: &ConfigExt, client: &mut R) -> Result<u32>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let min_participants = client
+                .get(&Self::MIN_PARTICIPANTS)
+                .log_err_at(
+                    config_ext.log_level(),
+                    "run raffle: error when getting min participants",
+                )?
+                .unwrap_or_default();
+            Ok(min_participants)
+        }
+
+        /// Builds the reply for the request: the raffle is skipped if there are not enough participants
+        fn build_reply(
+            &self,
+            config_ext: &ConfigExt,
+            request: &RequestSc,
+            min_participants: u32,
+        ) -> Result<ResponseMessage> {
+            let log_level = config_ext.log_level();
+            if !has_minimum_participants(request, min_participants, log_level) {
+                self.build_skipped_response(
+                    log_level,
+                    request,
+                    "insufficient participants".to_string(),
+                )
+            } else {
+                self.build_response(config_ext, request)
+            }
+        }
+
+        /// Computes the reply of the raffle, without sending it to the rollup anchor.
+        /// Returns the era and the encoded response, to be submitted by a relayer.
+        #[ink(message)]
+        pub fn prepare_raffle_reply(&self) -> Result<(u32, Vec<u8>)> {
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            self.ensure_trigger_allowed(&config_ext)?;
+            if config_ext.enforce_registry && !self.verify_core_against_registry()? {
+                log_error(
+                    config_ext.log_level(),
+                    format_args!("prepare raffle reply: the core js doesn't match the registry"),
+                );
+                return Err(ContractError::CoreHashMismatch);
+            }
+
+            let mut client = connect_read_anchor(config, &config_ext)?;
+            let (era, response) = self.compute_reply(&config_ext, &mut client)?;
+            Ok((era, encode_reply(&config_ext, &response)))
+        }
+
+        /// Processes a request with the core js and returns the response.
+        fn handle_request(
+            &self,
+            config_ext: &ConfigExt,
+            request_sc: &RequestSc,
+        ) -> Result<ResponseMessage> {
+            let Some(CoreJs {
+                script,
+                code_hash,
+                settings,
+                settings_hash,
+            }) = self.core_js.get()
+            else {
+                log_error(config_ext.log_level(), format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+            // zero hashes mean a corrupted config
+            if code_hash == CodeHash::default() || settings_hash == CodeHash::default() {
+                log_error(
+                    config_ext.log_level(),
+                    format_args!("CoreNotConfigured: zero hash"),
+                );
+                return Err(ContractError::CoreNotConfigured);
+            }
+
+            let output_value = self.run_script(config_ext, &script, settings, request_sc)?;
+            let input_hash = self.compute_input_hash(request_sc);
+            let response = ResponseMessage::JsResponse {
+                js_script_hash: code_hash,
+                input_hash,
+                settings_hash,
+                output_value,
+            };
+
+            Ok(response)
+        }
+
+        /// Runs the given script with the request and returns the encoded output for the smart contract.
+        fn run_script(
+            &self,
+            config_ext: &ConfigExt,
+            script: &str,
+            settings: String,
+            request_sc: &RequestSc,
+        ) -> Result<Vec<u8>> {
+            let request_js = convert_request(request_sc, config_ext.ss58_prefix());
+            let output_value_js =
+                self.run_js_inner(config_ext, script, &request_js.encode(), settings)?;
+
+            try_convert_output(output_value_js, config_ext)
+        }
+
+        /// Runs the same request with the core js and with a candidate script,
+        /// and returns both results without sending anything.
+        ///
+        /// For migration purpose. (admin only)
+        #[ink(message)]
+        pub fn dry_run_compare(
+            &self,
+            candidate_script: String,
+            candidate_settings: String,
+            era: u32,
+            nb_winners: u16,
+            excluded: Vec<AccountId>,
+        ) -> Result<(ResponseSc, ResponseSc)> {
+            self.ensure_owner()?;
+            let config_ext = self.config_ext();
+            let log_level = config_ext.log_level();
+            let Some(CoreJs {
+                script, settings, ..
+            }) = self.core_js.get()
+            else {
+                log_error(log_level, format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+
+            let request = RequestSc {
+                era,
+                nb_winners,
+                excluded,
+                participants: None,
+                rewards: None,
+            };
+            let decode = |output: Vec<u8>| {
+                ResponseSc::decode(&mut output.as_slice())
+                    .log_err_at(log_level, "failed to decode the response")
+                    .or(Err(ContractError::FailedToDecode))
+            };
+            let current = decode(self.run_script(&config_ext, &script, settings, &request)?)?;
+            let candidate = decode(self.run_script(
+                &config_ext,
+                &candidate_script,
+                candidate_settings,
+                &request,
+            )?)?;
+            Ok((current, candidate))
+        }
+
+        /// Gets the version of the reply format. None for the implicit version
+        #[ink(message)]
+        pub fn get_reply_format_version(&self) -> Option<u8> {
+            self.config_ext().reply_format_version
+        }
+
+        /// Sets the version of the reply format, added as first byte of the reply.
+        /// None for the implicit version (admin only)
+        #[ink(message)]
+        pub fn set_reply_format_version(&mut self, reply_format_version: Option<u8>) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.reply_format_version = reply_format_version)?;
+            Ok(())
+        }
+
+        /// Builds the response sent to the rollup anchor for the request.
+        fn build_response(
+            &self,
+            config_ext: &ConfigExt,
+            request_sc: &RequestSc,
+        ) -> Result<ResponseMessage> {
+            match self.handle_request(config_ext, request_sc) {
+                // reply the error of the js to the rollup anchor, to not block the queue
+                Err(error) => match replied_error(&error) {
+                    Some(error) => {
+                        self.build_error_response(config_ext.log_level(), request_sc, error)
+                    }
+                    None => Err(error),
+                },
+                result => result,
+            }
+        }
+
+        /// Computes the hash (sha2x256) of the response that would be sent to the rollup anchor,
+        /// without sending anything. It can be used as idempotency key for the draw.
+        /// The participants, the rewards and the minimum of participants are read in the rollup anchor,
+        /// as `run_raffle` does.
+        #[ink(message)]
+        pub fn compute_response_message_hash(
+            &self,
+            era: u32,
+            nb_winners: u16,
+            excluded: Vec<AccountId>,
+        ) -> Result<CodeHash> {
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let mut client = connect_read_anchor(config, &config_ext)?;
+            self.response_message_hash(&config_ext, &mut client, era, nb_winners, excluded)
+        }
+
+        /// Computes the hash of the reply for the given parameters and the values read with the client
+        fn response_message_hash<R>(
+            &self,
+            config_ext: &ConfigExt,
+            client: &mut R,
+            era: u32,
+            nb_winners: u16,
+            excluded: Vec<AccountId>,
+        ) -> Result<CodeHash>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let (participants, rewards) = self.read_participants_and_rewards(config_ext, client)?;
+            let min_participants = self.read_min_participants(config_ext, client)?;
+            let request = self.build_request(era, nb_winners, excluded, participants, rewards);
+            let response = self.build_reply(config_ext, &request, min_participants)?;
+            Ok(self
+                .env()
+                .hash_bytes::<ink::env::hash::Sha2x256>(&encode_reply(config_ext, &response)))
+        }
+
+        /// Builds the response sent when the raffle is skipped without running the core js.
+        fn build_skipped_response(
+            &self,
+            log_level: u8,
+            request_sc: &RequestSc,
+            skip_reason: String,
+        ) -> Result<ResponseMessage> {
+            let Some(CoreJs {
+                code_hash,
+                settings_hash,
+                ..
+            }) = self.core_js.get()
+            else {
+                log_error(log_level, format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+
+            log_info(
+                log_level,
+                format_args!("raffle skipped for era {}: {}", request_sc.era, skip_reason),
+            );
+            let output = ResponseSc {
+                era: request_sc.era,
+                skipped: true,
+                rewards: 0,
+                winners: Vec::new(),
+                skip_reason: Some(skip_reason),
+                winner_stakes: None,
+            };
+            Ok(ResponseMessage::JsResponse {
+                js_script_hash: code_hash,
+                input_hash: self.compute_input_hash(request_sc),
+                settings_hash,
+                output_value: output.encode(),
+            })
+        }
+
+        /// Builds the response sent when an error occurred in the core js.
+        fn build_error_response(
+            &self,
+            log_level: u8,
+            request_sc: &RequestSc,
+            error: String,
+        ) -> Result<ResponseMessage> {
+            let Some(CoreJs {
+                code_hash,
+                settings_hash,
+                ..
+            }) = self.core_js.get()
+            else {
+                log_error(log_level, format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+
+            log_error(
+                log_level,
+                format_args!("error in the core js for era {}: {}", request_sc.era, error),
+            );
+            Ok(ResponseMessage::Error {
+                js_script_hash: code_hash,
+                input_value: request_sc.encode(),
+                settings_hash,
+                error: error.into_bytes(),
+            })
+        }
+
+        /// Processes a request with the core js and returns the output.
+        fn run_js_inner(
+            &self,
+            config_ext: &ConfigExt,
+            js_code: &str,
+            request: &[u8],
+            settings: String,
+        ) -> Result<Vec<u8>> {
+            let args = alloc::vec![alloc::format!("0x{}", hex_fmt::HexFmt(request)), settings];
+            self.eval_js_inner(config_ext, js_code, &args)
+        }
+
+        /// Evaluates the js with the given arguments and returns the output.
+        fn eval_js_inner(
+            &self,
+            config_ext: &ConfigExt,
+            js_code: &str,
+            args: &[String],
+        ) -> Result<Vec<u8>> {
+            let log_level = config_ext.log_level();
+            check_js_input_len(js_code, args, config_ext.max_js_input_len, log_level)?;
+
+            let max_js_error_len = config_ext
+                .max_js_error_len
+                .unwrap_or(DEFAULT_MAX_JS_ERROR_LEN) as usize;
+
+            // the output of the js is mocked in the tests, the checks below are still applied
+            #[cfg(test)]
+            let output = match mock_js::get_output() {
+                Some(output) => Ok(phat_js::Output::Bytes(output)),
+                None => phat_js::eval(js_code, args),
+            };
+            #[cfg(not(test))]
+            let output = phat_js::eval(js_code, args);
+
+            let output = output
+                .log_err_at(log_level, "Failed to eval the core js")
+                .map_err(|e| {
+                    ContractError::JsError(
+                        classify_js_error(&e),
+                        format_js_error(&e, max_js_error_len),
+                    )
+                })?;
+
+            let output = output_to_bytes(output, config_ext.strict_bytes_output, log_level)?;
+            check_js_output_len(&output, config_ext.max_js_output_bytes, log_level)?;
+
+            Ok(output)
+        }
+
+        /// Evaluates any js with the given arguments, without sending anything.
+        ///
+        /// For debug purpose, only if the evaluation is allowed in the config. (admin only)
+        #[ink(message)]
+        pub fn eval_js(&self, script: String, args: Vec<String>) -> Result<Vec<u8>> {
+            self.ensure_owner()?;
+            self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            if !config_ext.allow_eval_js {
+                log_error(config_ext.log_level(), format_args!("EvalJsNotAllowed"));
+                return Err(ContractError::EvalJsNotAllowed);
+            }
+            self.eval_js_inner(&config_ext, &script, &args)
+        }
+
+        /// Returns true if the evaluation of any js is allowed
+        #[ink(message)]
+        pub fn get_allow_eval_js(&self) -> bool {
+            self.config_ext().allow_eval_js
+        }
+
+        /// Allows or forbids the evaluation of any js (admin only)
+        #[ink(message)]
+        pub fn set_allow_eval_js(&mut self, allow_eval_js: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.allow_eval_js = allow_eval_js)?;
+            Ok(())
+        }
+        /// Simulate the js, with the excluded addresses given in SS58 format
+        ///
+        /// For dev purpose. (admin only)
+        #[ink(message)]
+        pub fn dry_run_with_ss58(
+            &self,
+            era: u32,
+            nb_winners: u16,
+            excluded_ss58: Vec<String>,
+        ) -> Result<Vec<u8>> {
+            self.ensure_owner()?;
+            let config_ext = self.config_ext();
+            let expected_prefix = config_ext.expected_ss58_prefix();
+            let log_level = config_ext.log_level();
+            let excluded = excluded_ss58
+                .iter()
+                .map(|s| convert_address_output(s.as_str(), expected_prefix, log_level))
+                .collect::<Result<Vec<AccountId>>>()?;
+            self.dry_run_with_parameters(era, nb_winners, excluded)
+        }
+
+        /// Simulate the js
+        ///
+        /// For dev purpose. (admin only)
+        #[ink(message)]
+        pub fn dry_run_with_parameters(
+            &self,
+            era: u32,
+            nb_winners: u16,
+            excluded: Vec<AccountId>,
+        ) -> Result<Vec<u8>> {
+            self.ensure_owner()?;
+            let request = RequestSc {
+                era,
+                nb_winners,
+                excluded,
+                participants: None,
+                rewards: None,
+            };
+            self.dry_run_request(&self.config_ext(), &request)
+        }
+
+        /// Simulates the js with the given request and returns the encoded response
+        fn dry_run_request(&self, config_ext: &ConfigExt, request: &RequestSc) -> Result<Vec<u8>> {
+            let response = self.handle_request(config_ext, request)?;
+            let encoded_response = encode_reply(config_ext, &response);
+            log_debug(
+                config_ext.log_level(),
+                format_args!("encoded response : {:02x?}", encoded_response),
+            );
+            Ok(encoded_response)
+        }
+
+        /// Simulate the js
+        ///
+        /// For dev purpose. (admin only)
+        #[ink(message)]
+        pub fn dry_run(&self) -> Result<Vec<u8>> {
+            self.ensure_owner()?;
+
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let mut client = connect_read_anchor(config, &config_ext)?;
+
+            let request = self.read_request(&config_ext, &mut client)?;
+            let log_level = config_ext.log_level();
+            log_debug(
+                log_level,
+                format_args!("nb_winners : {:?}", request.nb_winners),
+            );
+            log_debug(log_level, format_args!("excluded : {:?}", request.excluded));
+            log_debug(
+                log_level,
+                format_args!("participants : {:?}", request.participants),
+            );
+
+            self.dry_run_request(&config_ext, &request)
+        }
+
+        /// Simulates the raffle with the request read from the rollup anchor
+        /// and an additional excluded account, and returns the result of the js.
+        ///
+        /// For dispute resolution. (admin only)
+        #[ink(message)]
+        pub fn dry_run_excluding(&self, additional: AccountId) -> Result<ResponseSc> {
+            self.ensure_owner()?;
+
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let log_level = config_ext.log_level();
+            let mut client = connect_read_anchor(config, &config_ext)?;
+
+            let mut request = self.read_request(&config_ext, &mut client)?;
+            if !request.excluded.contains(&additional) {
+                request.excluded.push(additional);
+            }
+
+            let Some(CoreJs {
+                script, settings, ..
+            }) = self.core_js.get()
+            else {
+                log_error(log_level, format_args!("CoreNotConfigured"));
+                return Err(ContractError::CoreNotConfigured);
+            };
+            let output = self.run_script(&config_ext, &script, settings, &request)?;
+            ResponseSc::decode(&mut output.as_slice())
+                .log_err_at(log_level, "failed to decode the response")
+                .or(Err(ContractError::FailedToDecode))
+        }
+
+        /// Reads the data from the rollup anchor and builds the request for the raffle
+        fn read_request<R>(&self, config_ext: &ConfigExt, client: &mut R) -> Result<RequestSc>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let log_level = config_ext.log_level();
+            let era = client
+                .get_raw(&Self::NEXT_ERA.encode())
+                .log_err_at(log_level, "run raffle: error when getting the next era")?;
+            let era = decode_next_era(era, log_level)?;
+
+            let nb_winners = client
+                .get_raw(&Self::NB_WINNERS.encode())
+                .log_err_at(log_level, "run raffle: error when getting nb winners")?;
+            let nb_winners =
+                resolve_nb_winners(nb_winners, config_ext.default_nb_winners, log_level)?;
+
+            let last_winners = client.get_raw(&Self::LAST_WINNERS.encode()).log_err_at(
+                log_level,
+                "run raffle: error when getting excluded addresses",
+            )?;
+            let last_winners = decode_last_winners(last_winners, log_level)?;
+
+            let (participants, rewards) = self.read_participants_and_rewards(config_ext, client)?;
+            Ok(self.build_request(era, nb_winners, last_winners, participants, rewards))
+        }
+
+        /// Reads the participants and the rewards in the rollup anchor, if they are included in the request
+        fn read_participants_and_rewards<R>(
+            &self,
+            config_ext: &ConfigExt,
+            client: &mut R,
+        ) -> Result<(Option<Vec<(AccountId, Balance)>>, Option<Balance>)>
+        where
+            R: RollupReader,
+            ContractError: From<R::Error>,
+        {
+            let log_level = config_ext.log_level();
+            let participants = if config_ext.read_participants_from_anchor {
+                let participants: Vec<(AccountId, Balance)> = client
+                    .get(&Self::PARTICIPANTS)
+                    .log_err_at(log_level, "run raffle: error when getting participants")?
+                    .unwrap_or_default();
+                Some(participants)
+            } else {
+                None
+            };
+
+            let rewards = if config_ext.include_rewards_in_request {
+                client
+                    .get(&Self::REWARDS)
+                    .log_err_at(log_level, "run raffle: error when getting rewards")?
+            } else {
+                None
+            };
+            Ok((participants, rewards))
+        }
+
+        /// Builds the request for the raffle from the values read in the rollup anchor
+        fn build_request(
+            &self,
+            era: u32,
+            nb_winners: u16,
+            last_winners: Vec<AccountId>,
+            participants: Option<Vec<(AccountId, Balance)>>,
+            rewards: Option<Balance>,
+        ) -> RequestSc {
+            RequestSc {
+                era,
+                nb_winners,
+                excluded: self.excluded_with_blocklist(last_winners),
+                participants,
+                rewards,
+            }
+        }
+
+        /// Adds the accounts in the blocklist to the excluded addresses, without duplicate
+        fn excluded_with_blocklist(&self, excluded: Vec<AccountId>) -> Vec<AccountId> {
+            let mut excluded = excluded;
+            for account in self.blocklist.get().unwrap_or_default() {
+                if !excluded.contains(&account) {
+                    excluded.push(account);
+                }
+            }
+            excluded
+        }
+
+        /// Gets the accounts never eligible to win
+        #[ink(message)]
+        pub fn get_blocklist(&self) -> Vec<AccountId> {
+            self.blocklist.get().unwrap_or_default()
+        }
+
+        /// Adds an account in the blocklist (admin only)
+        #[ink(message)]
+        pub fn add_to_blocklist(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let mut blocklist = self.blocklist.get().unwrap_or_default();
+            if !blocklist.contains(&account) {
+                blocklist.push(account);
+                self.blocklist.set(&blocklist);
+            }
+            Ok(())
+        }
+
+        /// Removes an account from the blocklist (admin only)
+        #[ink(message)]
+        pub fn remove_from_blocklist(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let mut blocklist = self.blocklist.get().unwrap_or_default();
+            blocklist.retain(|a| *a != account);
+            self.blocklist.set(&blocklist);
+            Ok(())
+        }
+
+        /// Returns BadOrigin if the triggers are restricted and the caller is not in the list
+        fn ensure_trigger_allowed(&self, config_ext: &ConfigExt) -> Result<()> {
+            if config_ext.restrict_triggers
+                && !self
+                    .triggers
+                    .get()
+                    .unwrap_or_default()
+                    .contains(&self.env().caller())
+            {
+                log_error(
+                    config_ext.log_level(),
+                    format_args!("run raffle: the caller is not allowed to run the raffle"),
+                );
+                return Err(ContractError::BadOrigin);
+            }
+            Ok(())
+        }
+
+        /// Gets the accounts allowed to run the raffle when the triggers are restricted
+        #[ink(message)]
+        pub fn get_triggers(&self) -> Vec<AccountId> {
+            self.triggers.get().unwrap_or_default()
+        }
+
+        /// Adds an account allowed to run the raffle (admin only)
+        #[ink(message)]
+        pub fn add_trigger(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let mut triggers = self.triggers.get().unwrap_or_default();
+            if !triggers.contains(&account) {
+                triggers.push(account);
+                self.triggers.set(&triggers);
+            }
+            Ok(())
+        }
+
+        /// Removes an account allowed to run the raffle (admin only)
+        #[ink(message)]
+        pub fn remove_trigger(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let mut triggers = self.triggers.get().unwrap_or_default();
+            triggers.retain(|a| *a != account);
+            self.triggers.set(&triggers);
+            Ok(())
+        }
+
+        /// Returns true if only the accounts in the triggers list can run the raffle
+        #[ink(message)]
+        pub fn get_restrict_triggers(&self) -> bool {
+            self.config_ext().restrict_triggers
+        }
+
+        /// Sets if only the accounts in the triggers list can run the raffle (admin only)
+        #[ink(message)]
+        pub fn set_restrict_triggers(&mut self, restrict_triggers: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.update_config_ext(|c| c.restrict_triggers = restrict_triggers)?;
+            Ok(())
+        }
+
+        /// Reads the u32 value saved in the kv store of the rollup anchor for the given key
+        ///
+        /// For debug purpose. (admin only)
+        #[ink(message)]
+        pub fn read_rollup_u32(&self, key_selector: u32) -> Result<Option<u32>> {
+            self.ensure_owner()?;
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let log_level = config_ext.log_level();
+            let mut client = connect(config, &config_ext)?;
+            let value = read_rollup_raw(&mut client, key_selector, log_level)?;
+            decode_rollup_value(value, log_level)
+        }
+
+        /// Reads the bytes saved in the kv store of the rollup anchor for the given key
+        ///
+        /// For debug purpose. (admin only)
+        #[ink(message)]
+        pub fn read_rollup_bytes(&self, key_selector: u32) -> Result<Option<Vec<u8>>> {
+            self.ensure_owner()?;
+            let config = self.ensure_client_configured()?;
+            let config_ext = self.config_ext();
+            let mut client = connect(config, &config_ext)?;
+            // the raw bytes are returned, without any decoding
+            read_rollup_raw(&mut client, key_selector, config_ext.log_level())
+        }
+
+        /// Computes the probability for each participant to be selected as winner, in basis points.
+        ///
+        /// Pure function: the participants and their weights must be provided by the caller.
+        /// See `compute_probabilities` for the approximation used.
+        #[ink(message)]
+        pub fn compute_probabilities(
+            &self,
+            participants: Vec<(AccountId, Balance)>,
+            nb_winners: u16,
+        ) -> Vec<(AccountId, u32)> {
+            compute_probabilities(&participants, nb_winners)
+        }
+
+        /// Encodes the request as sent to the rollup anchor
+        #[ink(message)]
+        pub fn encode_request_sc(&self, request: RequestSc) -> Vec<u8> {
+            request.encode()
+        }
+
+        /// Decodes the response as received by the rollup anchor
+        #[ink(message)]
+        pub fn decode_response_sc(&self, bytes: Vec<u8>) -> Result<ResponseSc> {
+            ResponseSc::decode(&mut bytes.as_slice()).or(Err(ContractError::FailedToDecode))
+        }
+
+        /// Splits the rewards among the winners, equally or proportionally to their stake.
+        ///
+        /// Pure function: see `split_rewards` for the rounding.
+        #[ink(message)]
+        pub fn split_rewards(
+            &self,
+            winners: Vec<(AccountId, Balance)>,
+            total: Balance,
+            mode: SplitMode,
+        ) -> Result<Vec<(AccountId, Balance)>> {
+            split_rewards(&winners, total, mode)
+        }
+
+        /// Computes the hash committing to the winners, whatever their order
+        #[ink(message)]
+        pub fn compute_winners_hash(&self, winners: Vec<AccountId>) -> CodeHash {
+            compute_winners_hash(winners)
+        }
+
+        /// Returns BadOrigin error if the caller is not the owner
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() == self.owner {
+                Ok(())
+            } else {
+                Err(ContractError::BadOrigin)
+            }
+        }
+
+        /// Returns the config reference or raise the error `ClientNotConfigured`
+        fn ensure_client_configured(&self) -> Result<&Config> {
+            self.config
+                .as_ref()
+                .ok_or(ContractError::ClientNotConfigured)
+        }
+
+        /// Returns the config mutable reference or raise the error `ClientNotConfigured`
+        fn ensure_client_configured_mut(&mut self) -> Result<&mut Config> {
+            self.config
+                .as_mut()
+                .ok_or(ContractError::ClientNotConfigured)
+        }
+
+        /// Returns the settings added after the first version of the config (default values if not set)
+        fn config_ext(&self) -> ConfigExt {
+            self.config_ext.get_or_default()
+        }
+
+        /// Updates the settings added after the first version of the config
+        /// or raise the error `ClientNotConfigured`
+        fn update_config_ext(&mut self, update: impl FnOnce(&mut ConfigExt)) -> Result<()> {
+            self.ensure_client_configured()?;
+            let mut config_ext = self.config_ext();
+            update(&mut config_ext);
+            self.config_ext.set(&config_ext);
+            Ok(())
+        }
+    }
+
+    const BASIS_POINTS: u32 = 10_000;
+
+    /// Default maximum length (in bytes) of the js error returned
+    const DEFAULT_MAX_JS_ERROR_LEN: u32 = 512;
+    /// Marker added at the end of a truncated js error
+    const TRUNCATED_MARKER: &str = "...";
+    /// Prefix of the error raised by the js engine when the script cannot be parsed
+    const JS_SYNTAX_ERROR_PREFIX: &str = "SyntaxError:";
+    /// Prefix of the error raised by the js engine when the evaluation is interrupted
+    const JS_TIMEOUT_ERROR_PREFIX: &str = "InternalError: interrupted";
+    /// Key in the local cache of the last era for which an error was replied
+    const ERROR_REPLIED_CACHE_KEY: &[u8] = b"error_replied_era";
+    /// Delay (in seconds) before replying again an error for the same era
+    const ERROR_REPLY_COOLDOWN_SECS: u64 = 3_600;
+    /// Log levels
+    const LOG_LEVEL_ERROR: u8 = 1;
+    const LOG_LEVEL_INFO: u8 = 2;
+    const LOG_LEVEL_DEBUG: u8 = 3;
+    /// Default number of decimals of the token
+    const DEFAULT_TOKEN_DECIMALS: u8 = 18;
+
+    /// Emits the log if its level is enabled by the configured log level
+    fn log(log_level: u8, level: u8, args: core::fmt::Arguments) {
+        if level > log_level {
+            return;
+        }
+        #[cfg(test)]
+        mock_log::record(level);
+        match level {
+            LOG_LEVEL_ERROR => error!("{}", args),
+            LOG_LEVEL_INFO => info!("{}", args),
+            _ => debug!("{}", args),
+        }
+    }
+
+    /// Logs an error if the log level allows it
+    fn log_error(log_level: u8, args: core::fmt::Arguments) {
+        log(log_level, LOG_LEVEL_ERROR, args);
+    }
+
+    /// Logs an info if the log level allows it
+    fn log_info(log_level: u8, args: core::fmt::Arguments) {
+        log(log_level, LOG_LEVEL_INFO, args);
+    }
+
+    /// Logs a debug message if the log level allows it
+    fn log_debug(log_level: u8, args: core::fmt::Arguments) {
+        log(log_level, LOG_LEVEL_DEBUG, args);
+    }
+
+    /// Same as `pink_extension::ResultExt`, but the error is only logged if the log level allows it
+    trait LogErr {
+        fn log_err_at(self, log_level: u8, message: &str) -> Self;
+    }
+
+    impl<T, E: core::fmt::Debug> LogErr for core::result::Result<T, E> {
+        fn log_err_at(self, log_level: u8, message: &str) -> Self {
+            if let Err(error) = &self {
+                log_error(log_level, format_args!("{message}: {error:?}"));
+            }
+            self
+        }
+    }
+
+    /// Formats the balance as a decimal string with the given number of decimals.
+    /// The trailing zeros of the fractional part are removed.
+    fn format_balance(value: Balance, decimals: u8) -> String {
+        let decimals = decimals as usize;
+        let digits = value.to_string();
+        // pad with zeros so there is at least one digit in the integer part
+        let digits = if digits.len() <= decimals {
+            alloc::format!("{}{}", "0".repeat(decimals + 1 - digits.len()), digits)
+        } else {
+            digits
+        };
+        let (integer, fraction) = digits.split_at(digits.len() - decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            integer.to_string()
+        } else {
+            alloc::format!("{integer}.{fraction}")
+        }
+    }
+
+    /// Settings of the core js stored as a flat json object of strings
+    #[derive(Deserialize)]
+    struct CoreJsSettings(BTreeMap<String, String>);
+
+    #[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum SplitMode {
+        /// all winners receive the same amount
+        Equal,
+        /// the winners receive an amount proportional to their stake
+        Proportional,
+    }
+
+    /// Splits the total rewards among the winners.
+    /// The remainder of the integer division is given to the first winner.
+    /// If the total stake is zero, the rewards are split equally.
+    /// Returns the error `StakeOverflow` if the sum of the stakes exceeds the balance type.
+    fn split_rewards(
+        winners: &[(AccountId, Balance)],
+        total: Balance,
+        mode: SplitMode,
+    ) -> Result<Vec<(AccountId, Balance)>> {
+        if winners.is_empty() {
+            return Ok(Vec::new());
+        }
+        let total_stake = checked_sum(winners.iter().map(|(_, stake)| *stake))?;
+
+        let mut shares: Vec<(AccountId, Balance)> = match mode {
+            SplitMode::Proportional if total_stake > 0 => winners
+                .iter()
+                .map(|(winner, stake)| {
+                    // use U256 to avoid an overflow, the result is lower than the total
+                    let share = U256::from(total) * U256::from(*stake) / U256::from(total_stake);
+                    (*winner, share.as_u128())
+                })
+                .collect(),
+            _ => {
+                let share = total / winners.len() as Balance;
+                winners.iter().map(|(winner, _)| (*winner, share)).collect()
+            }
+        };
+
+        let distributed = checked_sum(shares.iter().map(|(_, share)| *share))?;
+        shares[0].1 = total
+            .checked_sub(distributed)
+            .and_then(|remainder| shares[0].1.checked_add(remainder))
+            .ok_or(ContractError::StakeOverflow)?;
+        Ok(shares)
+    }
+
+    /// Sums the balances or raises the error `StakeOverflow`
+    fn checked_sum(values: impl Iterator<Item = Balance>) -> Result<Balance> {
+        values
+            .try_fold(0 as Balance, |sum, value| sum.checked_add(value))
+            .ok_or(ContractError::StakeOverflow)
+    }
+
+    /// Sha2x256 hash of the SCALE-encoded sorted list of winners
+    fn compute_winners_hash(mut winners: Vec<AccountId>) -> CodeHash {
+        winners.sort();
+        let mut output = CodeHash::default();
+        ink::env::hash_bytes::<ink::env::hash::Sha2x256>(&winners.encode(), &mut output);
+        output
+    }
+
+    /// Decodes the next era saved in the rollup anchor.
+    /// A missing value (likely an empty queue) is distinguished from a malformed one.
+    fn decode_next_era(value: Option<Vec<u8>>, log_level: u8) -> Result<u32> {
+        let Some(value) = value else {
+            log_info(
+                log_level,
+                format_args!("next era unknown: no era in the queue"),
+            );
+            return Err(ContractError::NextEraUnknown);
+        };
+        if value.len() != 4 {
+            log_error(
+                log_level,
+                format_args!("next era: unexpected length {}", value.len()),
+            );
+            return Err(ContractError::NextEraDecodeFailed);
+        }
+        u32::decode(&mut value.as_slice()).map_err(|_| {
+            log_error(
+                log_level,
+                format_args!("next era: failed to decode {:02x?}", value),
+            );
+            ContractError::NextEraDecodeFailed
+        })
+    }
+
+    /// Reads the raw value saved in the kv store of the rollup anchor for the given key
+    fn read_rollup_raw<R>(
+        client: &mut R,
+        key_selector: u32,
+        log_level: u8,
+    ) -> Result<Option<Vec<u8>>>
+    where
+        R: RollupReader,
+        ContractError: From<R::Error>,
+    {
+        let value = client
+            .get_raw(&key_selector.encode())
+            .log_err_at(log_level, "read rollup: error when getting the value")?;
+        Ok(value)
+    }
+
+    /// Decodes the raw value saved in the rollup anchor. The whole value must be consumed.
+    fn decode_rollup_value<T: Decode>(value: Option<Vec<u8>>, log_level: u8) -> Result<Option<T>> {
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let mut input = value.as_slice();
+        match T::decode(&mut input) {
+            Ok(decoded) if input.is_empty() => Ok(Some(decoded)),
+            _ => {
+                log_error(
+                    log_level,
+                    format_args!("rollup value: failed to decode {:02x?}", value),
+                );
+                Err(ContractError::FailedToDecode)
+            }
+        }
+    }
+
+    /// Decodes the last winners saved in the rollup anchor, excluded from the raffle.
+    /// A missing value means no exclusion, whereas a malformed one is rejected.
+    fn decode_last_winners(value: Option<Vec<u8>>, log_level: u8) -> Result<Vec<AccountId>> {
+        let Some(value) = value else {
+            return Ok(Vec::new());
+        };
+        let mut input = value.as_slice();
+        match Vec::<AccountId>::decode(&mut input) {
+            Ok(last_winners) if input.is_empty() => Ok(last_winners),
+            _ => {
+                log_error(
+                    log_level,
+                    format_args!("last winners: failed to decode {:02x?}", value),
+                );
+                Err(ContractError::ExcludedDecodeFailed)
+            }
+        }
+    }
+
+    /// Reads a value in the rollup anchor to check the client can reach it
+    fn ping_client<R>(client: &mut R, log_level: u8) -> Result<bool>
+    where
+        R: RollupReader,
+        ContractError: From<R::Error>,
+    {
+        client
+            .get_raw(&JsOffchainRollup::NEXT_ERA.encode())
+            .log_err_at(log_level, "ping rpc: failed to read the rollup anchor")?;
+        Ok(true)
+    }
+
+    /// Reads the number of winners configured in the rollup anchor, None if it is not set
+    fn read_nb_winners<R>(client: &mut R, log_level: u8) -> Result<Option<u16>>
+    where
+        R: RollupReader,
+        ContractError: From<R::Error>,
+    {
+        let nb_winners = client
+            .get_raw(&JsOffchainRollup::NB_WINNERS.encode())
+            .log_err_at(log_level, "get nb winners: error when getting the value")?;
+        nb_winners
+            .map(|v| decode_nb_winners(&v, log_level))
+            .transpose()
+    }
+
+    /// Decodes the number of winners saved in the rollup anchor.
+    /// The anchor stores the value as u16: a longer value is out of range, a shorter one is malformed.
+    fn decode_nb_winners(value: &[u8], log_level: u8) -> Result<u16> {
+        let expected_len = core::mem::size_of::<u16>();
+        if value.len() != expected_len {
+            log_error(
+                log_level,
+                format_args!(
+                    "nb winners: {} bytes instead of {expected_len}",
+                    value.len()
+                ),
+            );
+            return Err(if value.len() > expected_len {
+                ContractError::NbWinnersOutOfRange
+            } else {
+                ContractError::FailedToDecode
+            });
+        }
+        u16::decode(&mut &value[..])
+            .log_err_at(log_level, "nb winners: failed to decode the value")
+            .or(Err(ContractError::FailedToDecode))
+    }
+
+    /// Checks if there are enough participants to run the raffle. A minimum of 0 disables the check.
+    /// The participants are only known when they are read from the rollup anchor.
+    fn has_minimum_participants(
+        request_sc: &RequestSc,
+        min_participants: u32,
+        log_level: u8,
+    ) -> bool {
+        if min_participants == 0 {
+            return true;
+        }
+        let Some(participants) = request_sc.participants.as_ref() else {
+            log_info(
+                log_level,
+                format_args!(
+                    "min participants: the participants are not read from the anchor, no check"
+                ),
+            );
+            return true;
+        };
+        participants.len() >= min_participants as usize
+    }
+
+    /// Gets the number of winners from the value saved in the rollup anchor,
+    /// or the default one if the value is not set.
+    fn resolve_nb_winners(
+        value: Option<Vec<u8>>,
+        default: Option<u16>,
+        log_level: u8,
+    ) -> Result<u16> {
+        match (value, default) {
+            (Some(value), _) => decode_nb_winners(&value, log_level),
+            (None, Some(default)) => {
+                log_info(
+                    log_level,
+                    format_args!("nb winners not set, use the default value {default}"),
+                );
+                Ok(default)
+            }
+            (None, None) => {
+                log_error(log_level, format_args!("nb winners not set"));
+                Err(ContractError::NbWinnersNotSet)
+            }
+        }
+    }
+
+    /// Returns the error replied to the rollup anchor if the core js failed or returned an invalid output.
+    /// The local guards (input too large, timeout) and the errors of the contract are not replied.
+    fn replied_error(error: &ContractError) -> Option<String> {
+        match error {
+            ContractError::JsError(JsErrorKind::InputTooLarge | JsErrorKind::Timeout, _) => None,
+            ContractError::JsError(_, error) => Some(error.clone()),
+            ContractError::FailedToDecode
+            | ContractError::RewardsOutOfRange
+            | ContractError::RewardsNotSet
+            | ContractError::WrongSs58Version
+            | ContractError::InvalidAddressLength => Some(alloc::format!("{error:?}")),
+            _ => None,
+        }
+    }
+
+    /// Returns true if an error was replied for the era and the cooldown is not expired
+    fn error_replied_for_era(era: u32) -> bool {
+        pink_extension::ext().cache_get(ERROR_REPLIED_CACHE_KEY) == Some(era.encode())
+    }
+
+    /// Saves in the local cache the era for which an error was replied, until the end of the cooldown
+    fn save_error_replied(era: u32, log_level: u8) {
+        if pink_extension::ext()
+            .cache_set(ERROR_REPLIED_CACHE_KEY, &era.encode())
+            .is_err()
+        {
+            log_error(
+                log_level,
+                format_args!("failed to save the era {era} in the cache"),
+            );
+            return;
+        }
+        pink_extension::ext()
+            .cache_set_expiration(ERROR_REPLIED_CACHE_KEY, ERROR_REPLY_COOLDOWN_SECS);
+    }
+
+    /// Checks the length of the script and the arguments given to the js does not exceed the limit.
+    fn check_js_input_len(
+        js_code: &str,
+        args: &[String],
+        max_len: Option<u32>,
+        log_level: u8,
+    ) -> Result<()> {
+        let Some(max_len) = max_len else {
+            return Ok(());
+        };
+        let len = args
+            .iter()
+            .fold(js_code.len(), |len, arg| len.saturating_add(arg.len()));
+        if len > max_len as usize {
+            log_error(
+                log_level,
+                format_args!("the input of the js is too large: {len} bytes"),
+            );
+            return Err(ContractError::JsError(
+                JsErrorKind::InputTooLarge,
+                "input too large".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the length of the output of the js does not exceed the limit.
+    fn check_js_output_len(output: &[u8], max_len: Option<u32>, log_level: u8) -> Result<()> {
+        let Some(max_len) = max_len else {
+            return Ok(());
+        };
+        if output.len() > max_len as usize {
+            log_error(
+                log_level,
+                format_args!("the output of the js is too large: {} bytes", output.len()),
+            );
+            return Err(ContractError::JsError(
+                JsErrorKind::BadOutput,
+                "output too large".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Converts the output of the js into bytes.
+    /// In strict mode, only the bytes are accepted.
+    fn output_to_bytes(
+        output: phat_js::Output,
+        strict_bytes_output: bool,
+        log_level: u8,
+    ) -> Result<Vec<u8>> {
+        match output {
+            phat_js::Output::String(_) if strict_bytes_output => {
+                log_error(
+                    log_level,
+                    format_args!("the js returned a string instead of bytes"),
+                );
+                Err(ContractError::JsError(
+                    JsErrorKind::BadOutput,
+                    "expected Bytes output".to_string(),
+                ))
+            }
+            phat_js::Output::String(s) => Ok(s.into_bytes()),
+            phat_js::Output::Bytes(b) => Ok(b),
+            phat_js::Output::Undefined => Err(ContractError::JsError(
+                JsErrorKind::BadOutput,
+                "Undefined output".to_string(),
+            )),
+        }
+    }
+
+    /// Classifies the error returned by `phat_js::eval` with the prefix added by the js engine.
+    /// The message of the error is not inspected: a runtime error can contain any text.
+    fn classify_js_error(error: &str) -> JsErrorKind {
+        let error = error.trim_start();
+        if error.starts_with(JS_SYNTAX_ERROR_PREFIX) {
+            JsErrorKind::Syntax
+        } else if error.starts_with(JS_TIMEOUT_ERROR_PREFIX) {
+            JsErrorKind::Timeout
+        } else {
+            JsErrorKind::Runtime
+        }
+    }
+
+    /// Truncates the js error to `max_len` bytes (marker included) on a char boundary.
+    /// The marker is omitted when `max_len` is too small to contain it.
+    fn format_js_error(error: &str, max_len: usize) -> String {
+        if error.len() <= max_len {
+            return error.to_string();
+        }
+        let marker = if max_len < TRUNCATED_MARKER.len() {
+            ""
+        } else {
+            TRUNCATED_MARKER
+        };
+        let mut end = max_len - marker.len();
+        while !error.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut truncated = error[..end].to_string();
+        truncated.push_str(marker);
+        truncated
+    }
+
+    /// Approximates the probability (in basis points) for each participant to be selected
+    /// when `nb_winners` winners are drawn proportionally to the weights, without replacement.
+    ///
+    /// The probability of a participant is `nb_winners * weight / total_weight`, capped to 100%.
+    /// The winners reaching the cap are removed and the remaining draws are spread again among
+    /// the other participants, so the probabilities sum up to `nb_winners`.
+    /// It is an approximation of the exact inclusion probabilities, good enough for display.
+    fn compute_probabilities(
+        participants: &[(AccountId, Balance)],
+        nb_winners: u16,
+    ) -> Vec<(AccountId, u32)> {
+        let mut probabilities: Vec<Option<u32>> = participants
+            .iter()
+            .map(|(_, weight)| if *weight == 0 { Some(0) } else { None })
+            .collect();
+
+        let mut remaining_winners = nb_winners as u128;
+        loop {
+            let remaining_weight: Balance = participants
+                .iter()
+                .zip(probabilities.iter())
+                .filter(|(_, p)| p.is_none())
+                .fold(0, |total, ((_, weight), _)| total.saturating_add(*weight));
+
+            if remaining_weight == 0 {
+                break;
+            }
+
+            // the participants reaching 100% are sure to win
+            let mut capped = false;
+            for ((_, weight), probability) in participants.iter().zip(probabilities.iter_mut()) {
+                if probability.is_none()
+                    && remaining_winners.saturating_mul(*weight) >= remaining_weight
+                {
+                    *probability = Some(BASIS_POINTS);
+                    remaining_winners = remaining_winners.saturating_sub(1);
+                    capped = true;
+                }
+            }
+
+            if !capped {
+                for ((_, weight), probability) in participants.iter().zip(probabilities.iter_mut())
+                {
+                    if probability.is_none() {
+                        let p = remaining_winners
+                            .saturating_mul(*weight)
+                            .saturating_mul(BASIS_POINTS as u128)
+                            / remaining_weight;
+                        *probability = Some(p as u32);
+                    }
+                }
+                break;
+            }
+        }
+
+        participants
+            .iter()
+            .zip(probabilities)
+            .map(|((account, _), p)| (*account, p.unwrap_or_default()))
+            .collect()
+    }
+
+    /// Parses the code hash published by the registry as an hex string (with or without 0x prefix)
+    fn parse_code_hash(body: &[u8]) -> Result<CodeHash> {
+        let body = core::str::from_utf8(body).or(Err(ContractError::FailedToDecode))?;
+        let body = body.trim();
+        let body = body.strip_prefix("0x").unwrap_or(body);
+        let mut code_hash = CodeHash::default();
+        hex::decode_to_slice(body, &mut code_hash).or(Err(ContractError::FailedToDecode))?;
+        Ok(code_hash)
+    }
+
+    /// Creates the rollup client with the primary RPC endpoint, or the fallback one if it fails
+    fn connect(config: &Config, config_ext: &ConfigExt) -> Result<InkRollupClient> {
+        let log_level = config_ext.log_level();
+        match (
+            connect_rpc(config, &config.rpc, log_level),
+            config_ext.fallback_rpc.as_ref(),
+        ) {
+            (Err(_), Some(fallback_rpc)) => {
+                let client = connect_rpc(config, fallback_rpc, log_level)?;
+                log_info(
+                    log_level,
+                    format_args!("rollup client created with the fallback rpc {fallback_rpc}"),
+                );
+                Ok(client)
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Gets the rpc and the contract id of the anchor where the data are read,
+    /// if it differs from the target contract
+    fn read_anchor<'a>(
+        config: &'a Config,
+        config_ext: &'a ConfigExt,
+    ) -> Option<(&'a str, ContractId)> {
+        let read_contract_id = config_ext.read_contract_id?;
+        let read_rpc = config_ext
+            .read_rpc
+            .as_deref()
+            .unwrap_or(config.rpc.as_str());
+        Some((read_rpc, read_contract_id))
+    }
+
+    /// Creates the rollup client for the anchor where the data are read.
+    /// The target contract is used if no read anchor is configured.
+    fn connect_read_anchor(config: &Config, config_ext: &ConfigExt) -> Result<InkRollupClient> {
+        match read_anchor(config, config_ext) {
+            Some((rpc, contract_id)) => {
+                connect_anchor(config, rpc, &contract_id, config_ext.log_level())
+            }
+            None => connect(config, config_ext),
+        }
+    }
+
+    fn connect_rpc(config: &Config, rpc: &str, log_level: u8) -> Result<InkRollupClient> {
+        connect_anchor(config, rpc, &config.contract_id, log_level)
+    }
+
+    fn connect_anchor(
+        config: &Config,
+        rpc: &str,
+        contract_id: &ContractId,
+        log_level: u8,
+    ) -> Result<InkRollupClient> {
+        let result = InkRollupClient::new(rpc, config.pallet_id, config.call_id, contract_id)
+            .log_err_at(log_level, "failed to create rollup client");
+
+        match result {
+            Ok(client) => Ok(client),
+            Err(e) => {
+                log_error(log_level, format_args!("Error : {:?}", e));
+                Err(ContractError::FailedToCreateClient)
+            }
+        }
+    }
+
+    /// Runs the action with the primary rpc, and with the fallback rpc if the primary rpc fails
+    fn with_fallback_rpc<T>(
+        config: &Config,
+        config_ext: &ConfigExt,
+        mut action: impl FnMut(&str) -> Result<T>,
+    ) -> Result<T> {
+        match (action(&config.rpc), config_ext.fallback_rpc.as_ref()) {
+            (
+                Err(ContractError::FailedToCreateClient | ContractError::FailedToCommitTx),
+                Some(fallback_rpc),
+            ) => {
+                log_info(
+                    config_ext.log_level(),
+                    format_args!("retry with the fallback rpc {fallback_rpc}"),
+                );
+                action(fallback_rpc)
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Reads the values saved in the kv store of the rollup anchor
+    trait RollupReader {
+        type Error: core::fmt::Debug;
+
+        fn get_raw(&mut self, key: &[u8]) -> core::result::Result<Option<Vec<u8>>, Self::Error>;
+
+        fn get<V: Decode>(&mut self, key: &u32) -> core::result::Result<Option<V>, Self::Error>;
+    }
+
+    /// Sends the reply in a transaction to the rollup anchor
+    trait RollupWriter {
+        fn commit_reply(
+            self,
+            reply: Vec<u8>,
+            attest_key: &[u8; 32],
+            sender_key: Option<&[u8; 32]>,
+            log_level: u8,
+        ) -> Result<Option<Vec<u8>>>;
+    }
+
+    impl RollupReader for InkRollupClient {
+        type Error = phat_offchain_rollup::Error;
+
+        fn get_raw(&mut self, key: &[u8]) -> core::result::Result<Option<Vec<u8>>, Self::Error> {
+            InkRollupClient::get_raw(self, key)
+        }
+
+        fn get<V: Decode>(&mut self, key: &u32) -> core::result::Result<Option<V>, Self::Error> {
+            InkRollupClient::get(self, key)
+        }
+    }
+
+    impl RollupWriter for InkRollupClient {
+        fn commit_reply(
+            mut self,
+            reply: Vec<u8>,
+            attest_key: &[u8; 32],
+            sender_key: Option<&[u8; 32]>,
+            log_level: u8,
+        ) -> Result<Option<Vec<u8>>> {
+            // Attach an action to the tx by:
+            self.action(Action::Reply(reply));
+
+            maybe_submit_tx(self, attest_key, sender_key, log_level)
+        }
+    }
+
+    fn maybe_submit_tx(
+        client: InkRollupClient,
         attest_key: &[u8; 32],
         sender_key: Option<&[u8; 32]>,
+        log_level: u8,
     ) -> Result<Option<Vec<u8>>> {
         let maybe_submittable = client
             .commit()
-            .log_err("failed to commit")
+            .log_err_at(log_level, "failed to commit")
             .map_err(|_| ContractError::FailedToCommitTx)?;
 
-        if let Some(submittable) = maybe_submittable {
-            let tx_id = if let Some(sender_key) = sender_key {
-                // Prefer to meta-tx
-                submittable
-                    .submit_meta_tx(attest_key, sender_key)
-                    .log_err("failed to submit rollup meta-tx")?
-            } else {
-                // Fallback to account-based authentication
-                submittable
-                    .submit(attest_key)
-                    .log_err("failed to submit rollup tx")?
+        if let Some(submittable) = maybe_submittable {
+            let tx_id = if let Some(sender_key) = sender_key {
+                // Prefer to meta-tx
+                submittable
+                    .submit_meta_tx(attest_key, sender_key)
+                    .log_err_at(log_level, "failed to submit rollup meta-tx")?
+            } else {
+                // Fallback to account-based authentication
+                submittable
+                    .submit(attest_key)
+                    .log_err_at(log_level, "failed to submit rollup tx")?
+            };
+            return Ok(Some(tx_id));
+        }
+        Ok(None)
+    }
+
+    /// The optional fields are always encoded, after the fields of the previous versions:
+    /// the consumer of the error reply still decodes the era, the number of winners and the excluded addresses.
+    #[derive(Encode, Decode, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RequestSc {
+        era: u32,
+        nb_winners: u16,
+        excluded: Vec<AccountId>,
+        /// participants read from the rollup anchor (optional)
+        participants: Option<Vec<(AccountId, Balance)>>,
+        /// rewards read from the rollup anchor (optional)
+        rewards: Option<Balance>,
+    }
+
+    /// The optional fields are always encoded: the js reads them, even when they are not set.
+    #[derive(Encode)]
+    pub struct RequestJs {
+        era: u32,
+        nb_winners: u16,
+        excluded: Vec<String>,
+        /// participants read from the rollup anchor (optional)
+        participants: Option<Vec<(String, Balance)>>,
+        /// rewards read from the rollup anchor, as a decimal string (optional)
+        rewards: Option<String>,
+    }
+
+    /// SS58 prefix used by default: Astar
+    fn default_ss58_prefix() -> u16 {
+        Ss58AddressFormat::from(Ss58AddressFormatRegistry::AstarAccount).prefix()
+    }
+
+    fn convert_address_input(address: &AccountId, ss58_prefix: u16) -> String {
+        let address_hex: [u8; 32] = scale::Encode::encode(&address)
+            .try_into()
+            .expect("incorrect length");
+        AccountId32::from(address_hex)
+            .to_ss58check_with_version(Ss58AddressFormat::custom(ss58_prefix))
+    }
+
+    /// Sorts the excluded addresses and the participants so the encoding doesn't depend on their order
+    fn sort_request(request_sc: &RequestSc) -> RequestSc {
+        let mut excluded = request_sc.excluded.clone();
+        excluded.sort();
+        let participants = request_sc.participants.clone().map(|mut participants| {
+            participants.sort();
+            participants
+        });
+        RequestSc {
+            era: request_sc.era,
+            nb_winners: request_sc.nb_winners,
+            excluded,
+            participants,
+            rewards: request_sc.rewards,
+        }
+    }
+
+    fn convert_request(request_sc: &RequestSc, ss58_prefix: u16) -> RequestJs {
+        let request_sc = &sort_request(request_sc);
+        let era = request_sc.era;
+        let nb_winners = request_sc.nb_winners;
+        let excluded = request_sc
+            .excluded
+            .iter()
+            .map(|address| convert_address_input(address, ss58_prefix))
+            .collect();
+        let participants = request_sc.participants.as_ref().map(|participants| {
+            participants
+                .iter()
+                .map(|(address, balance)| (convert_address_input(address, ss58_prefix), *balance))
+                .collect()
+        });
+        let rewards = request_sc.rewards.map(|rewards| rewards.to_string());
+        RequestJs {
+            era,
+            nb_winners,
+            excluded,
+            participants,
+            rewards,
+        }
+    }
+
+    /// The optional fields are always provided by the js, even when they are not set.
+    #[derive(scale::Encode, scale::Decode)]
+    pub struct ResponseJs {
+        pub era: u32,
+        pub skipped: bool,
+        pub rewards: Balance,
+        pub winners: Vec<String>,
+        /// why the raffle has been skipped (optional)
+        pub skip_reason: Option<String>,
+        /// stakes of the winners, in the same order (optional)
+        pub winner_stakes: Option<Vec<Balance>>,
+    }
+
+    #[derive(scale::Encode, scale::Decode, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ResponseSc {
+        pub era: u32,
+        pub skipped: bool,
+        pub rewards: Balance,
+        pub winners: Vec<AccountId>,
+        /// why the raffle has been skipped (optional)
+        pub skip_reason: Option<String>,
+        /// stakes of the winners, in the same order (optional)
+        pub winner_stakes: Option<Vec<Balance>>,
+    }
+
+    /// Converts the SS58 address into an AccountId.
+    /// If an expected prefix is given (strict mode), the address must be encoded with this prefix.
+    fn convert_address_output(
+        address: &str,
+        expected_prefix: Option<u16>,
+        log_level: u8,
+    ) -> Result<AccountId> {
+        let (account_id, version) = AccountId32::from_ss58check_with_version(address)
+            .log_err_at(log_level, "incorrect address")
+            .or(Err(ContractError::FailedToDecode))?;
+        if let Some(expected_prefix) = expected_prefix {
+            if version.prefix() != expected_prefix {
+                log_error(
+                    log_level,
+                    format_args!(
+                        "address {address} encoded with the prefix {} instead of {expected_prefix}",
+                        version.prefix()
+                    ),
+                );
+                return Err(ContractError::WrongSs58Version);
+            }
+        }
+        let address_hex: [u8; 32] = scale::Encode::encode(&account_id)
+            .try_into()
+            .or(Err(ContractError::InvalidAddressLength))?;
+        Ok(AccountId::from(address_hex))
+    }
+
+    /// Encodes the reply sent to the rollup anchor, with the version of the format if configured
+    fn encode_reply(config_ext: &ConfigExt, response: &ResponseMessage) -> Vec<u8> {
+        match config_ext.reply_format_version {
+            Some(version) => (version, response).encode(),
+            None => response.encode(),
+        }
+    }
+
+    fn try_convert_output(output: Vec<u8>, config: &ConfigExt) -> Result<Vec<u8>> {
+        let log_level = config.log_level();
+        let mut input = output.as_slice();
+        let output_js = ResponseJs::decode(&mut input)
+            .log_err_at(log_level, "failed to convert js output")
+            .or(Err(ContractError::FailedToDecode))?;
+        // the whole output must be consumed, otherwise the js and the contract disagree on the format
+        if !input.is_empty() {
+            log_error(
+                log_level,
+                format_args!("{} unexpected bytes after the js output", input.len()),
+            );
+            return Err(ContractError::FailedToDecode);
+        }
+        let era = output_js.era;
+        let skipped = output_js.skipped;
+        let rewards = output_js.rewards;
+        if let Some(max_rewards) = config.max_rewards {
+            if rewards > max_rewards {
+                log_error(
+                    log_level,
+                    format_args!("rewards {rewards} above the limit {max_rewards}"),
+                );
+                return Err(ContractError::RewardsOutOfRange);
+            }
+        }
+        if config.require_rewards && !skipped && rewards == 0 {
+            log_error(log_level, format_args!("no rewards for the era {era}"));
+            return Err(ContractError::RewardsNotSet);
+        }
+        let mut winners = output_js
+            .winners
+            .iter()
+            .map(|s| convert_address_output(s.as_str(), config.expected_ss58_prefix(), log_level))
+            .collect::<Result<Vec<AccountId>>>()?;
+        let mut winner_stakes = output_js.winner_stakes;
+        if let Some(stakes) = winner_stakes.as_ref() {
+            if stakes.len() != winners.len() {
+                log_error(
+                    log_level,
+                    format_args!("{} stakes for {} winners", stakes.len(), winners.len()),
+                );
+                return Err(ContractError::FailedToDecode);
+            }
+        }
+        if config.sort_winners {
+            match winner_stakes.take() {
+                // keep each stake aligned with its winner
+                Some(stakes) => {
+                    let mut pairs: Vec<(AccountId, Balance)> =
+                        winners.into_iter().zip(stakes).collect();
+                    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+                    let (sorted_winners, sorted_stakes) = pairs.into_iter().unzip();
+                    winners = sorted_winners;
+                    winner_stakes = Some(sorted_stakes);
+                }
+                None => winners.sort(),
+            }
+        }
+        let skip_reason = if skipped { output_js.skip_reason } else { None };
+        let output_sc = ResponseSc {
+            era,
+            skipped,
+            rewards,
+            winners,
+            skip_reason,
+            winner_stakes,
+        };
+
+        Ok(output_sc.encode())
+    }
+
+    /// Injection point used in the tests to replace the output of the core js,
+    /// because the JS Contract is not accessible inner the tests
+    #[cfg(test)]
+    mod mock_js {
+        use alloc::vec::Vec;
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static MOCK_JS_OUTPUT: RefCell<Option<Vec<u8>>> = RefCell::new(None);
+        }
+
+        /// Sets the output returned instead of evaluating the core js. None to evaluate the js.
+        pub fn set_output(output: Option<Vec<u8>>) {
+            MOCK_JS_OUTPUT.with(|o| *o.borrow_mut() = output);
+        }
+
+        pub fn get_output() -> Option<Vec<u8>> {
+            MOCK_JS_OUTPUT.with(|o| o.borrow().clone())
+        }
+    }
+
+    /// Sink of the logs emitted in the tests, to check the log level is applied
+    #[cfg(test)]
+    mod mock_log {
+        use alloc::vec::Vec;
+        use core::cell::RefCell;
+
+        std::thread_local! {
+            static MOCK_LOGS: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+        }
+
+        /// Records the level of an emitted log
+        pub fn record(level: u8) {
+            MOCK_LOGS.with(|l| l.borrow_mut().push(level));
+        }
+
+        /// Returns the levels of the logs emitted since the last call
+        pub fn take() -> Vec<u8> {
+            MOCK_LOGS.with(|l| l.take())
+        }
+    }
+
+    /// Rollup clients used in the tests instead of the rollup anchor, which is not accessible inner the tests
+    #[cfg(test)]
+    mod mock_rollup {
+        use super::{ContractError, Result, RollupReader, RollupWriter};
+        use alloc::{collections::BTreeMap, rc::Rc, vec::Vec};
+        use core::cell::RefCell;
+        use scale::{Decode, Encode};
+
+        /// Kv store of the anchor: the keys read and the replies committed are shared between the clones
+        #[derive(Default, Clone)]
+        pub struct MockRollupClient {
+            values: BTreeMap<Vec<u8>, Vec<u8>>,
+            reads: Rc<RefCell<Vec<Vec<u8>>>>,
+            commits: Rc<RefCell<Vec<Vec<u8>>>>,
+            fail_read: bool,
+            fail_commit: bool,
+        }
+
+        impl MockRollupClient {
+            /// Saves the encoded value under the key
+            pub fn with_value(mut self, key: u32, value: impl Encode) -> Self {
+                self.values.insert(key.encode(), value.encode());
+                self
+            }
+
+            /// The reads fail as if the rpc was down
+            pub fn failing_read(mut self) -> Self {
+                self.fail_read = true;
+                self
+            }
+
+            /// The commit of the reply fails as if the rpc was down
+            pub fn failing_commit(mut self) -> Self {
+                self.fail_commit = true;
+                self
+            }
+
+            /// Returns the keys read by the client
+            pub fn reads(&self) -> Vec<Vec<u8>> {
+                self.reads.borrow().clone()
+            }
+
+            /// Returns the replies committed by the client
+            pub fn commits(&self) -> Vec<Vec<u8>> {
+                self.commits.borrow().clone()
+            }
+        }
+
+        impl RollupReader for MockRollupClient {
+            type Error = ContractError;
+
+            fn get_raw(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+                if self.fail_read {
+                    return Err(ContractError::FailedToCallRollup);
+                }
+                self.reads.borrow_mut().push(key.to_vec());
+                Ok(self.values.get(key).cloned())
+            }
+
+            fn get<V: Decode>(&mut self, key: &u32) -> Result<Option<V>> {
+                self.get_raw(&key.encode())?
+                    .map(|v| V::decode(&mut v.as_slice()).or(Err(ContractError::FailedToDecode)))
+                    .transpose()
+            }
+        }
+
+        impl RollupWriter for MockRollupClient {
+            fn commit_reply(
+                self,
+                reply: Vec<u8>,
+                _attest_key: &[u8; 32],
+                _sender_key: Option<&[u8; 32]>,
+                _log_level: u8,
+            ) -> Result<Option<Vec<u8>>> {
+                if self.fail_commit {
+                    return Err(ContractError::FailedToCommitTx);
+                }
+                self.commits.borrow_mut().push(reply);
+                Ok(None)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use pink_extension::PinkEnvironment;
+
+        struct EnvVars {
+            /// The RPC endpoint of the target blockchain
+            rpc: String,
+            pallet_id: u8,
+            call_id: u8,
+            /// The rollup anchor address on the target blockchain
+            contract_id: ContractId,
+            /// When we want to manually set the attestor key for signing the message (only dev purpose)
+            attest_key: Vec<u8>,
+            /// When we want to use meta tx
+            sender_key: Option<Vec<u8>>,
+        }
+
+        fn get_env(key: &str) -> String {
+            std::env::var(key).expect("env not found")
+        }
+
+        fn config() -> EnvVars {
+            dotenvy::dotenv().ok();
+            let rpc = get_env("RPC");
+            let pallet_id: u8 = get_env("PALLET_ID").parse().expect("u8 expected");
+            let call_id: u8 = get_env("CALL_ID").parse().expect("u8 expected");
+            let contract_id: ContractId = hex::decode(get_env("CONTRACT_ID"))
+                .expect("hex decode failed")
+                .try_into()
+                .expect("incorrect length");
+            let attest_key = hex::decode(get_env("ATTEST_KEY")).expect("hex decode failed");
+            let sender_key = std::env::var("SENDER_KEY")
+                .map(|s| hex::decode(s).expect("hex decode failed"))
+                .ok();
+
+            EnvVars {
+                rpc: rpc.to_string(),
+                pallet_id,
+                call_id,
+                contract_id: contract_id.into(),
+                attest_key,
+                sender_key,
+            }
+        }
+
+        fn init_contract() -> JsOffchainRollup {
+            let EnvVars {
+                rpc,
+                pallet_id,
+                call_id,
+                contract_id,
+                attest_key,
+                sender_key,
+            } = config();
+
+            let mut oracle = JsOffchainRollup::default();
+            oracle
+                .config_target_contract(rpc, pallet_id, call_id, contract_id.into(), sender_key)
+                .unwrap();
+            //oracle.set_attest_key(Some(attest_key)).unwrap();
+
+            oracle
+        }
+
+        #[ink::test]
+        #[ignore = "The JS Contract is not accessible inner the test"]
+        fn run_raffle() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+
+            let r = oracle.run_raffle().expect("failed to run raffle");
+            ink::env::debug_println!("answer request: {r:?}");
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn read_rollup_values() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+
+            let next_era = oracle
+                .read_rollup_u32(JsOffchainRollup::NEXT_ERA)
+                .expect("failed to read next era");
+            ink::env::debug_println!("next era: {next_era:?}");
+            assert!(next_era.is_some());
+
+            let unknown = oracle
+                .read_rollup_bytes(ink::selector_id!("UNKNOWN_KEY"))
+                .expect("failed to read unknown key");
+            assert_eq!(unknown, None);
+        }
+
+        #[ink::test]
+        fn read_rollup_values_with_mock_client() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut client = mock_rollup::MockRollupClient::default()
+                .with_value(JsOffchainRollup::NEXT_ERA, 4517u32)
+                .with_value(JsOffchainRollup::NB_WINNERS, 2u16);
+
+            let next_era =
+                read_rollup_raw(&mut client, JsOffchainRollup::NEXT_ERA, LOG_LEVEL_DEBUG);
+            assert_eq!(next_era, Ok(Some(4517u32.encode())));
+            assert_eq!(
+                decode_rollup_value::<u32>(next_era.unwrap(), LOG_LEVEL_DEBUG),
+                Ok(Some(4517))
+            );
+
+            // the value is not a u32
+            let nb_winners =
+                read_rollup_raw(&mut client, JsOffchainRollup::NB_WINNERS, LOG_LEVEL_DEBUG);
+            assert_eq!(
+                decode_rollup_value::<u32>(nb_winners.unwrap(), LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToDecode)
+            );
+
+            let unknown = read_rollup_raw(
+                &mut client,
+                ink::selector_id!("UNKNOWN_KEY"),
+                LOG_LEVEL_DEBUG,
+            );
+            assert_eq!(unknown, Ok(None));
+            assert_eq!(
+                decode_rollup_value::<u32>(unknown.unwrap(), LOG_LEVEL_DEBUG),
+                Ok(None)
+            );
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn connect_with_fallback_rpc() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let EnvVars {
+                rpc,
+                pallet_id,
+                call_id,
+                contract_id,
+                ..
+            } = config();
+
+            let mut oracle = JsOffchainRollup::default();
+            oracle
+                .config_target_contract(
+                    "http://127.0.0.1:1".to_string(),
+                    pallet_id,
+                    call_id,
+                    contract_id.into(),
+                    None,
+                )
+                .unwrap();
+
+            // the primary rpc is unreachable
+            let config = oracle.ensure_client_configured().unwrap();
+            assert!(connect(config, &oracle.config_ext()).is_err());
+
+            // the fallback rpc is used
+            oracle.set_fallback_rpc(Some(rpc)).unwrap();
+            let config = oracle.ensure_client_configured().unwrap();
+            assert!(connect(config, &oracle.config_ext()).is_ok());
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn get_nb_winners() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+
+            let nb_winners = oracle.get_nb_winners().expect("failed to read nb winners");
+            ink::env::debug_println!("nb winners: {nb_winners:?}");
+            assert!(nb_winners.is_some());
+        }
+
+        #[ink::test]
+        fn get_nb_winners_with_mock_client() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut client = mock_rollup::MockRollupClient::default()
+                .with_value(JsOffchainRollup::NB_WINNERS, 2u16);
+            assert_eq!(read_nb_winners(&mut client, LOG_LEVEL_DEBUG), Ok(Some(2)));
+
+            // the number of winners is not set in the anchor
+            let mut client = mock_rollup::MockRollupClient::default();
+            assert_eq!(read_nb_winners(&mut client, LOG_LEVEL_DEBUG), Ok(None));
+        }
+
+        #[ink::test]
+        fn get_nb_winners_not_configured() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = JsOffchainRollup::default();
+            assert_eq!(
+                oracle.get_nb_winners(),
+                Err(ContractError::ClientNotConfigured)
+            );
+        }
+
+        #[ink::test]
+        #[ignore = "The JS Contract is not accessible inner the test"]
+        fn eval_js() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract.set_allow_eval_js(true).unwrap();
+
+            let output = contract
+                .eval_js("new Uint8Array([1, 2, 3])".to_string(), vec![])
+                .expect("failed to eval the js");
+            assert_eq!(output, vec![1, 2, 3]);
+
+            let result = contract.eval_js("throw new Error('boom')".to_string(), vec![]);
+            assert!(matches!(
+                result,
+                Err(ContractError::JsError(JsErrorKind::Runtime, _))
+            ));
+
+            let result = contract.eval_js("function (".to_string(), vec![]);
+            assert!(matches!(
+                result,
+                Err(ContractError::JsError(JsErrorKind::Syntax, _))
+            ));
+        }
+
+        #[ink::test]
+        fn eval_js_not_allowed() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            assert!(!contract.get_allow_eval_js());
+
+            let result = contract.eval_js("'0x01'".to_string(), vec![]);
+            assert_eq!(result, Err(ContractError::EvalJsNotAllowed));
+
+            contract.set_allow_eval_js(true).unwrap();
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            let result = contract.eval_js("'0x01'".to_string(), vec![]);
+            assert_eq!(result, Err(ContractError::BadOrigin));
+        }
+
+        #[ink::test]
+        fn test_hash_algo() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_hash_algo(), HashAlgo::Sha2x256);
+
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let sha2_core_js = contract.get_core_js().unwrap();
+
+            contract.set_hash_algo(HashAlgo::Blake2x256).unwrap();
+            assert_eq!(contract.get_hash_algo(), HashAlgo::Blake2x256);
+            let blake2_core_js = contract.get_core_js().unwrap();
+
+            // the hashes are computed again with the new algorithm
+            assert_ne!(sha2_core_js.code_hash, blake2_core_js.code_hash);
+            assert_ne!(sha2_core_js.settings_hash, blake2_core_js.settings_hash);
+            let mut expected_code_hash = CodeHash::default();
+            ink::env::hash_bytes::<ink::env::hash::Blake2x256>(b"script", &mut expected_code_hash);
+            assert_eq!(blake2_core_js.code_hash, expected_code_hash);
+
+            contract.set_hash_algo(HashAlgo::Sha2x256).unwrap();
+            let core_js = contract.get_core_js().unwrap();
+            assert_eq!(core_js.code_hash, sha2_core_js.code_hash);
+            assert_eq!(core_js.settings_hash, sha2_core_js.settings_hash);
+        }
+
+        fn last_core_js_configured_event() -> CoreJsConfigured {
+            type Event = <JsOffchainRollup as ::ink::reflect::ContractEventBase>::Type;
+            let event = ink::env::test::recorded_events()
+                .last()
+                .expect("no event emitted");
+            let Event::CoreJsConfigured(event) =
+                <Event as Decode>::decode(&mut &event.data[..]).expect("failed to decode event");
+            event
+        }
+
+        #[ink::test]
+        fn test_core_js_configured_event() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = JsOffchainRollup::default();
+
+            contract
+                .config_core_js("script_1".to_string(), "settings_1".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+            let event = last_core_js_configured_event();
+            assert_eq!(event.code_hash, core_js.code_hash);
+            assert_eq!(event.settings_hash, core_js.settings_hash);
+
+            contract
+                .config_core_js_script("script_2".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+            let event = last_core_js_configured_event();
+            assert_eq!(event.code_hash, core_js.code_hash);
+            assert_eq!(event.settings_hash, core_js.settings_hash);
+
+            contract
+                .config_core_js_settings("settings_2".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+            let event = last_core_js_configured_event();
+            assert_eq!(event.code_hash, core_js.code_hash);
+            assert_eq!(event.settings_hash, core_js.settings_hash);
+
+            assert_eq!(ink::env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn test_two_step_ownership_transfer() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = JsOffchainRollup::default();
+
+            contract.propose_owner(accounts.bob).unwrap();
+            assert_eq!(contract.owner(), accounts.alice);
+            assert_eq!(contract.pending_owner(), Some(accounts.bob));
+
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            contract.accept_ownership().unwrap();
+            assert_eq!(contract.owner(), accounts.bob);
+            assert_eq!(contract.pending_owner(), None);
+        }
+
+        #[ink::test]
+        fn test_accept_ownership_by_wrong_account() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = JsOffchainRollup::default();
+
+            contract.propose_owner(accounts.bob).unwrap();
+
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.charlie);
+            let result = contract.accept_ownership();
+            assert!(matches!(result, Err(ContractError::BadOrigin)));
+            assert_eq!(contract.owner(), accounts.alice);
+            assert_eq!(contract.pending_owner(), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn test_cancel_ownership_transfer() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = JsOffchainRollup::default();
+
+            contract.propose_owner(accounts.bob).unwrap();
+            contract.cancel_ownership_transfer().unwrap();
+            assert_eq!(contract.pending_owner(), None);
+
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            let result = contract.accept_ownership();
+            assert!(matches!(result, Err(ContractError::BadOrigin)));
+            assert_eq!(contract.owner(), accounts.alice);
+        }
+
+        #[ink::test]
+        fn test_config_ext_kept_when_reconfigured() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = JsOffchainRollup::default();
+            let result = contract.set_max_rewards(Some(1_000));
+            assert!(matches!(result, Err(ContractError::ClientNotConfigured)));
+
+            let mut contract = init_contract_with_dummy_config();
+            contract.set_max_rewards(Some(1_000)).unwrap();
+            contract
+                .config_target_contract(
+                    "http://localhost:9944".to_string(),
+                    70,
+                    6,
+                    [2u8; 32].to_vec(),
+                    None,
+                )
+                .unwrap();
+            assert_eq!(contract.get_max_rewards(), Some(1_000));
+        }
+
+        #[ink::test]
+        fn test_decode_config_ext_stored_by_previous_version() {
+            // nothing stored by the first version
+            let config_ext = ConfigExt::decode(&mut &b""[..]).unwrap();
+            assert_eq!(config_ext.max_rewards, None);
+
+            // stored by a version with the max rewards only
+            let stored = Some(1_000 as Balance).encode();
+            let config_ext = ConfigExt::decode(&mut stored.as_slice()).unwrap();
+            assert_eq!(config_ext.max_rewards, Some(1_000));
+        }
+
+        fn init_contract_with_dummy_config() -> JsOffchainRollup {
+            let mut contract = JsOffchainRollup::default();
+            contract
+                .config_target_contract(
+                    "http://localhost:9944".to_string(),
+                    70,
+                    6,
+                    [1u8; 32].to_vec(),
+                    None,
+                )
+                .unwrap();
+            contract
+        }
+
+        #[ink::test]
+        fn test_ping_rpc_not_configured() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            assert_eq!(contract.ping_rpc(), Err(ContractError::ClientNotConfigured));
+        }
+
+        #[ink::test]
+        fn test_target_contract_getters() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            assert_eq!(contract.get_rpc(), None);
+            assert_eq!(contract.get_pallet_id(), None);
+            assert_eq!(contract.get_call_id(), None);
+            assert_eq!(contract.get_contract_id(), None);
+
+            let contract = init_contract_with_dummy_config();
+            assert_eq!(
+                contract.get_rpc(),
+                Some("http://localhost:9944".to_string())
+            );
+            assert_eq!(contract.get_pallet_id(), Some(70));
+            assert_eq!(contract.get_call_id(), Some(6));
+            assert_eq!(contract.get_contract_id(), Some([1u8; 32]));
+        }
+
+        #[ink::test]
+        fn test_read_anchor() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            // the target contract is used by default
+            assert_eq!(contract.get_read_anchor(), None);
+
+            // same rpc, other contract
+            contract
+                .config_read_anchor(Some([2u8; 32].to_vec()), None)
+                .unwrap();
+            assert_eq!(
+                contract.get_read_anchor(),
+                Some(("http://localhost:9944".to_string(), [2u8; 32]))
+            );
+
+            // other rpc, other contract
+            contract
+                .config_read_anchor(
+                    Some([2u8; 32].to_vec()),
+                    Some("http://localhost:9945".to_string()),
+                )
+                .unwrap();
+            assert_eq!(
+                contract.get_read_anchor(),
+                Some(("http://localhost:9945".to_string(), [2u8; 32]))
+            );
+            // the reply is still sent to the target contract
+            assert_eq!(contract.get_contract_id(), Some([1u8; 32]));
+
+            assert_eq!(
+                contract.config_read_anchor(Some([2u8; 31].to_vec()), None),
+                Err(ContractError::InvalidAddressLength)
+            );
+
+            contract.config_read_anchor(None, None).unwrap();
+            assert_eq!(contract.get_read_anchor(), None);
+        }
+
+        #[ink::test]
+        fn test_rotate_sender_key() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_sender_address(), None);
+
+            let sender_address = contract.rotate_sender_key([2u8; 32].to_vec()).unwrap();
+            assert_eq!(contract.get_sender_address(), Some(sender_address.clone()));
+
+            let new_sender_address = contract.rotate_sender_key([3u8; 32].to_vec()).unwrap();
+            assert_ne!(sender_address, new_sender_address);
+            assert_eq!(contract.get_sender_address(), Some(new_sender_address));
+        }
+
+        #[ink::test]
+        fn test_sender_key_must_differ_from_attest_key() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let attest_key = contract.attest_key;
+
+            // identical keys
+            assert_eq!(
+                contract.rotate_sender_key(attest_key.to_vec()),
+                Err(ContractError::KeysMustDiffer)
+            );
+            assert_eq!(
+                contract.config_target_contract(
+                    "http://localhost:9944".to_string(),
+                    70,
+                    6,
+                    [1u8; 32].to_vec(),
+                    Some(attest_key.to_vec()),
+                ),
+                Err(ContractError::KeysMustDiffer)
+            );
+            assert_eq!(contract.get_sender_address(), None);
+
+            // distinct keys
+            let mut other_key = attest_key;
+            other_key[0] ^= 1;
+            assert!(contract.rotate_sender_key(other_key.to_vec()).is_ok());
+            assert_eq!(
+                contract.config_target_contract(
+                    "http://localhost:9944".to_string(),
+                    70,
+                    6,
+                    [1u8; 32].to_vec(),
+                    Some(other_key.to_vec()),
+                ),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn test_get_sender_ss58() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_sender_ss58(), None);
+
+            let sender = contract.rotate_sender_key([2u8; 32].to_vec()).unwrap();
+            let sender: [u8; 32] = sender.try_into().unwrap();
+            let sender = AccountId::from(sender);
+            assert_eq!(
+                contract.get_sender_ss58(),
+                Some(convert_address_input(&sender, default_ss58_prefix()))
+            );
+
+            contract.config_ss58(Some(0), false).unwrap();
+            assert_eq!(
+                contract.get_sender_ss58(),
+                Some(convert_address_input(&sender, 0))
+            );
+        }
+
+        #[ink::test]
+        fn test_verify_ecdsa_address() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            let ecdsa_address = contract.get_attest_ecdsa_address();
+            assert!(contract.verify_ecdsa_address(ecdsa_address.clone()));
+
+            let mut wrong_address = ecdsa_address;
+            wrong_address[0] ^= 1;
+            assert!(!contract.verify_ecdsa_address(wrong_address));
+            assert!(!contract.verify_ecdsa_address(vec![]));
+        }
+
+        #[ink::test]
+        fn test_signing_model() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_signing_model(), SigningModel::AttestorOnly);
+
+            let sender = contract.rotate_sender_key([2u8; 32].to_vec()).unwrap();
+            assert_eq!(
+                contract.get_signing_model(),
+                SigningModel::MetaTx { sender }
+            );
+        }
+
+        #[ink::test]
+        fn test_rotate_sender_key_invalid_length() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let result = contract.rotate_sender_key([2u8; 31].to_vec());
+            assert!(matches!(result, Err(ContractError::InvalidKeyLength)));
+            assert_eq!(contract.get_sender_address(), None);
+        }
+
+        #[ink::test]
+        fn test_compute_probabilities_equal_weights() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let participants = vec![
+                (accounts.alice, 100),
+                (accounts.bob, 100),
+                (accounts.charlie, 100),
+                (accounts.django, 100),
+            ];
+
+            let probabilities = compute_probabilities(&participants, 2);
+            assert_eq!(
+                probabilities,
+                vec![
+                    (accounts.alice, 5_000),
+                    (accounts.bob, 5_000),
+                    (accounts.charlie, 5_000),
+                    (accounts.django, 5_000),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn test_compute_probabilities_skewed_weights() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let participants = vec![
+                (accounts.alice, 900),
+                (accounts.bob, 50),
+                (accounts.charlie, 50),
+                (accounts.django, 0),
+            ];
+
+            let probabilities = compute_probabilities(&participants, 1);
+            assert_eq!(
+                probabilities,
+                vec![
+                    (accounts.alice, 9_000),
+                    (accounts.bob, 500),
+                    (accounts.charlie, 500),
+                    (accounts.django, 0),
+                ]
+            );
+
+            // the whale is sure to win and the second draw is shared between the others
+            let probabilities = compute_probabilities(&participants, 2);
+            assert_eq!(
+                probabilities,
+                vec![
+                    (accounts.alice, 10_000),
+                    (accounts.bob, 5_000),
+                    (accounts.charlie, 5_000),
+                    (accounts.django, 0),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn test_version() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            assert_eq!(contract.version(), (2, 0, 0));
+        }
+
+        fn mock_registry(code_hash: CodeHash) {
+            use pink_extension::chain_extension::{mock, HttpResponse};
+            mock::mock_http_request(move |_| {
+                HttpResponse::ok(alloc::format!("0x{}", hex_fmt::HexFmt(code_hash)).into_bytes())
+            });
+        }
+
+        #[ink::test]
+        fn test_verify_core_against_registry() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_registry(Some("https://registry.local".to_string()), true)
+                .unwrap();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let code_hash = contract.get_core_js().unwrap().code_hash;
+
+            // the registry publishes the same code hash
+            mock_registry(code_hash);
+            assert_eq!(contract.verify_core_against_registry(), Ok(true));
+
+            // the registry publishes another code hash
+            mock_registry([1u8; 32]);
+            assert_eq!(contract.verify_core_against_registry(), Ok(false));
+            let result = contract.run_raffle();
+            assert!(matches!(result, Err(ContractError::CoreHashMismatch)));
+        }
+
+        #[ink::test]
+        fn test_parse_code_hash() {
+            let body = alloc::format!("0x{}\n", "01".repeat(32));
+            assert_eq!(parse_code_hash(body.as_bytes()), Ok([1u8; 32]));
+
+            let body = "02".repeat(32);
+            assert_eq!(parse_code_hash(body.as_bytes()), Ok([2u8; 32]));
+
+            assert_eq!(
+                parse_code_hash(b"0x0102"),
+                Err(ContractError::FailedToDecode)
+            );
+        }
+
+        #[ink::test]
+        fn test_encode_request_with_participants() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+
+            // without participants, the optional fields are encoded as None
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
+            };
+            let encoded_request = request.encode();
+            assert_eq!(
+                encoded_request,
+                (
+                    4517u32,
+                    2u16,
+                    vec![accounts.alice],
+                    None::<Vec<(AccountId, Balance)>>,
+                    None::<Balance>
+                )
+                    .encode()
+            );
+            assert_eq!(
+                RequestSc::decode(&mut encoded_request.as_slice()).unwrap(),
+                request
+            );
+
+            // participants read from the anchor
+            let participants = vec![(accounts.bob, 100), (accounts.charlie, 50)];
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: Some(participants.clone()),
+                rewards: None,
+            };
+            let encoded_request = request.encode();
+            assert_eq!(
+                RequestSc::decode(&mut encoded_request.as_slice()).unwrap(),
+                request
+            );
+            // the consumer of the error reply decodes the fields of the previous versions only
+            assert_eq!(
+                <(u32, u16, Vec<AccountId>)>::decode(&mut encoded_request.as_slice()).unwrap(),
+                (4517, 2, vec![accounts.alice])
+            );
+
+            let request_js = convert_request(&request, default_ss58_prefix());
+            let prefix = default_ss58_prefix();
+            assert_eq!(
+                request_js.encode(),
+                (
+                    4517u32,
+                    2u16,
+                    vec![convert_address_input(&accounts.alice, prefix)],
+                    Some(vec![
+                        (convert_address_input(&accounts.bob, prefix), 100 as Balance),
+                        (
+                            convert_address_input(&accounts.charlie, prefix),
+                            50 as Balance
+                        ),
+                    ]),
+                    None::<String>,
+                )
+                    .encode()
+            );
+        }
+
+        #[ink::test]
+        fn test_encode_request_with_rewards() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let prefix = default_ss58_prefix();
+
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: Some(163483092786717962675),
+            };
+            let encoded_request = request.encode();
+            assert_eq!(
+                RequestSc::decode(&mut encoded_request.as_slice()).unwrap(),
+                request
+            );
+            assert_eq!(
+                convert_request(&request, prefix).encode(),
+                (
+                    4517u32,
+                    2u16,
+                    vec![convert_address_input(&accounts.alice, prefix)],
+                    None::<Vec<(String, Balance)>>,
+                    Some("163483092786717962675".to_string()),
+                )
+                    .encode()
+            );
+
+            // the rewards are encoded as None when they are not read from the anchor
+            let request = RequestSc {
+                rewards: None,
+                ..request
+            };
+            assert_eq!(
+                convert_request(&request, prefix).encode(),
+                (
+                    4517u32,
+                    2u16,
+                    vec![convert_address_input(&accounts.alice, prefix)],
+                    None::<Vec<(String, Balance)>>,
+                    None::<String>,
+                )
+                    .encode()
+            );
+        }
+
+        #[ink::test]
+        fn test_request_order_independent() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let contract = init_contract_with_dummy_config();
+
+            let request_1 = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice, accounts.bob, accounts.charlie],
+                participants: Some(vec![(accounts.django, 10), (accounts.eve, 20)]),
+                rewards: None,
+            };
+            let request_2 = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.charlie, accounts.alice, accounts.bob],
+                participants: Some(vec![(accounts.eve, 20), (accounts.django, 10)]),
+                rewards: None,
+            };
+
+            assert_eq!(
+                convert_request(&request_1, default_ss58_prefix()).encode(),
+                convert_request(&request_2, default_ss58_prefix()).encode()
+            );
+            assert_eq!(
+                contract.compute_input_hash(&request_1),
+                contract.compute_input_hash(&request_2)
+            );
+        }
+
+        #[ink::test]
+        fn test_encode_decode_round_trip() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let contract = JsOffchainRollup::default();
+
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice, accounts.bob],
+                participants: None,
+                rewards: None,
+            };
+            let encoded_request = contract.encode_request_sc(request);
+            let decoded_request = RequestSc::decode(&mut encoded_request.as_slice()).unwrap();
+            assert_eq!(
+                decoded_request,
+                RequestSc {
+                    era: 4517,
+                    nb_winners: 2,
+                    excluded: vec![accounts.alice, accounts.bob],
+                    participants: None,
+                    rewards: None,
+                }
+            );
+
+            let response = ResponseSc {
+                era: 4517,
+                skipped: false,
+                rewards: 163483092786717962675,
+                winners: vec![accounts.charlie, accounts.django, accounts.eve],
+                skip_reason: None,
+                winner_stakes: None,
+            };
+            let decoded_response = contract.decode_response_sc(response.encode());
+            assert_eq!(decoded_response, Ok(response));
+
+            let result = contract.decode_response_sc(vec![1, 2, 3]);
+            assert_eq!(result, Err(ContractError::FailedToDecode));
+        }
+
+        #[ink::test]
+        fn test_format_js_error_oversized() {
+            let error = "e".repeat(1000);
+            let formatted = format_js_error(&error, 512);
+            assert_eq!(formatted.len(), 512);
+            assert!(formatted.ends_with(TRUNCATED_MARKER));
+
+            // a truncation in the middle of a multi bytes char
+            let error = "é".repeat(10);
+            let formatted = format_js_error(&error, 8);
+            assert_eq!(formatted, "éé...");
+
+            // no truncation
+            let formatted = format_js_error("SyntaxError", 512);
+            assert_eq!(formatted, "SyntaxError");
+
+            // no room for the marker
+            assert_eq!(format_js_error("SyntaxError", 2), "Sy");
+            assert_eq!(format_js_error("SyntaxError", 0), "");
+            assert_eq!(format_js_error("é", 1), "");
+            assert_eq!(format_js_error("SyntaxError", 3), "...");
+        }
+
+        #[ink::test]
+        fn test_output_to_bytes() {
+            let string_output = || phat_js::Output::String("0x01".to_string());
+            let bytes_output = || phat_js::Output::Bytes(vec![1, 2, 3]);
+
+            // lenient mode
+            assert_eq!(
+                output_to_bytes(string_output(), false, LOG_LEVEL_DEBUG),
+                Ok(b"0x01".to_vec())
+            );
+            assert_eq!(
+                output_to_bytes(bytes_output(), false, LOG_LEVEL_DEBUG),
+                Ok(vec![1, 2, 3])
+            );
+
+            // strict mode
+            assert_eq!(
+                output_to_bytes(string_output(), true, LOG_LEVEL_DEBUG),
+                Err(ContractError::JsError(
+                    JsErrorKind::BadOutput,
+                    "expected Bytes output".to_string()
+                ))
+            );
+            assert_eq!(
+                output_to_bytes(bytes_output(), true, LOG_LEVEL_DEBUG),
+                Ok(vec![1, 2, 3])
+            );
+
+            assert_eq!(
+                output_to_bytes(phat_js::Output::Undefined, false, LOG_LEVEL_DEBUG),
+                Err(ContractError::JsError(
+                    JsErrorKind::BadOutput,
+                    "Undefined output".to_string()
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn test_compute_winners_hash() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+
+            let hash = compute_winners_hash(vec![accounts.alice, accounts.bob, accounts.charlie]);
+            // same winners in another order
+            assert_eq!(
+                hash,
+                compute_winners_hash(vec![accounts.charlie, accounts.alice, accounts.bob])
+            );
+            // one winner changed
+            assert_ne!(
+                hash,
+                compute_winners_hash(vec![accounts.alice, accounts.bob, accounts.django])
+            );
+        }
+
+        #[ink::test]
+        fn test_blocklist() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = JsOffchainRollup::default();
+
+            contract.add_to_blocklist(accounts.charlie).unwrap();
+            contract.add_to_blocklist(accounts.django).unwrap();
+            contract.add_to_blocklist(accounts.charlie).unwrap();
+            assert_eq!(
+                contract.get_blocklist(),
+                vec![accounts.charlie, accounts.django]
+            );
+
+            // the blocklisted accounts are excluded even if they are not in the last winners
+            let excluded = contract.excluded_with_blocklist(vec![accounts.alice, accounts.django]);
+            assert_eq!(
+                excluded,
+                vec![accounts.alice, accounts.django, accounts.charlie]
+            );
+
+            contract.remove_from_blocklist(accounts.charlie).unwrap();
+            assert_eq!(contract.get_blocklist(), vec![accounts.django]);
+            let excluded = contract.excluded_with_blocklist(vec![accounts.alice]);
+            assert_eq!(excluded, vec![accounts.alice, accounts.django]);
+
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            let result = contract.add_to_blocklist(accounts.eve);
+            assert_eq!(result, Err(ContractError::BadOrigin));
+        }
+
+        #[ink::test]
+        fn test_triggers() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+
+            contract.add_trigger(accounts.bob).unwrap();
+            contract.add_trigger(accounts.bob).unwrap();
+            assert_eq!(contract.get_triggers(), vec![accounts.bob]);
+
+            // unrestricted mode: anyone can run the raffle
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.charlie);
+            assert_eq!(
+                contract.ensure_trigger_allowed(&contract.config_ext()),
+                Ok(())
+            );
+
+            // restricted mode: only the triggers can run the raffle
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.alice);
+            contract.set_restrict_triggers(true).unwrap();
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.charlie);
+            assert_eq!(contract.run_raffle(), Err(ContractError::BadOrigin));
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.ensure_trigger_allowed(&contract.config_ext()),
+                Ok(())
+            );
+
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.alice);
+            contract.remove_trigger(accounts.bob).unwrap();
+            assert_eq!(contract.get_triggers(), vec![]);
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            assert_eq!(contract.run_raffle(), Err(ContractError::BadOrigin));
+
+            let result = contract.add_trigger(accounts.bob);
+            assert_eq!(result, Err(ContractError::BadOrigin));
+        }
+
+        #[ink::test]
+        fn test_get_core_js_setting() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = JsOffchainRollup::default();
+            assert_eq!(contract.get_core_js_setting("graphApi".to_string()), None);
+
+            let settings = r#"{"graphApi": "https://query.substrate.fi/lucky-subquery-shibuya", "network": "shibuya"}"#;
+            contract
+                .config_core_js("script".to_string(), settings.to_string())
+                .unwrap();
+
+            // present key
+            assert_eq!(
+                contract.get_core_js_setting("graphApi".to_string()),
+                Some("https://query.substrate.fi/lucky-subquery-shibuya".to_string())
+            );
+            assert_eq!(
+                contract.get_core_js_setting("network".to_string()),
+                Some("shibuya".to_string())
+            );
+            // absent key
+            assert_eq!(contract.get_core_js_setting("rpc".to_string()), None);
+
+            // the settings must be a flat json object
+            let settings = r#"{"graphApi": "https://query.substrate.fi/lucky-subquery-shibuya", "network": {"name": "shibuya"}}"#;
+            contract
+                .config_core_js_settings(settings.to_string())
+                .unwrap();
+            assert_eq!(contract.get_core_js_setting("graphApi".to_string()), None);
+
+            // malformed settings
+            contract
+                .config_core_js_settings(
+                    "https://query.substrate.fi/lucky-subquery-shibuya".to_string(),
+                )
+                .unwrap();
+            assert_eq!(contract.get_core_js_setting("graphApi".to_string()), None);
+            contract
+                .config_core_js_settings(r#"{"graphApi": "#.to_string())
+                .unwrap();
+            assert_eq!(contract.get_core_js_setting("graphApi".to_string()), None);
+        }
+
+        #[ink::test]
+        fn test_convert_address() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let address_hex: [u8; 32] =
+                hex::decode("bc5a6b58324a633175374b57464a42357476554b3364774e4673454132436e66")
+                    .expect("hex decode failed")
+                    .try_into()
+                    .expect("incorrect length");
+            let address = AccountId::from(address_hex);
+
+            let astar_address_str = convert_address_input(&address, default_ss58_prefix());
+            assert_eq!(
+                astar_address_str,
+                "aCG9z4XcZrSUfrzuaUYWwxKruA6rnA8z9wMcZtDQEfPRQLH"
+            );
+
+            assert_eq!(
+                Ok(address),
+                convert_address_output(&astar_address_str, None, LOG_LEVEL_DEBUG)
+            );
+        }
+
+        #[ink::test]
+        fn test_encode_ss58_multi() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let pubkey: [u8; 32] =
+                hex::decode("bc5a6b58324a633175374b57464a42357476554b3364774e4673454132436e66")
+                    .expect("hex decode failed")
+                    .try_into()
+                    .expect("incorrect length");
+
+            let contract = JsOffchainRollup::default();
+            // Astar, Shiden and Shibuya share the same prefix (5)
+            assert_eq!(
+                contract.encode_ss58_multi(pubkey, vec![5, 0, 42]),
+                vec![
+                    (
+                        5,
+                        "aCG9z4XcZrSUfrzuaUYWwxKruA6rnA8z9wMcZtDQEfPRQLH".to_string()
+                    ),
+                    (
+                        0,
+                        "15Fxs2MWh7UohNqgNv5RdSdCdUSdas1Y3TAhY9fi5xgx1oi8".to_string()
+                    ),
+                    (
+                        42,
+                        "5GKfih6SqLDLFqqARH2RVHo3mrSytZTPxxSDNrgMXsfRqd8u".to_string()
+                    ),
+                ]
+            );
+            assert_eq!(contract.encode_ss58_multi(pubkey, vec![]), vec![]);
+        }
+
+        #[ink::test]
+        fn test_validate_ss58_batch() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let address = AccountId::from([1u8; 32]);
+            let astar_address = convert_address_input(&address, 5);
+            let polkadot_address = convert_address_input(&address, 0);
+            let addresses = vec![
+                astar_address.clone(),
+                polkadot_address.clone(),
+                "not an address".to_string(),
+                "".to_string(),
+            ];
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(
+                contract.validate_ss58_batch(addresses.clone()),
+                vec![
+                    (astar_address.clone(), true),
+                    (polkadot_address.clone(), true),
+                    ("not an address".to_string(), false),
+                    ("".to_string(), false),
+                ]
+            );
+
+            // in strict mode, only the Astar address is valid
+            contract.config_ss58(None, true).unwrap();
+            assert_eq!(
+                contract.validate_ss58_batch(addresses),
+                vec![
+                    (astar_address, true),
+                    (polkadot_address, false),
+                    ("not an address".to_string(), false),
+                    ("".to_string(), false),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn test_decode_ss58_checked() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            let address = AccountId::from([1u8; 32]);
+            let astar_address = convert_address_input(&address, 5);
+            let polkadot_address = convert_address_input(&address, 0);
+
+            // allowed prefix
+            assert_eq!(
+                contract.decode_ss58_checked(astar_address.clone(), vec![5, 42]),
+                Ok(address)
+            );
+            // disallowed prefix
+            assert_eq!(
+                contract.decode_ss58_checked(polkadot_address, vec![5, 42]),
+                Err(ContractError::WrongSs58Version)
+            );
+            assert_eq!(
+                contract.decode_ss58_checked(astar_address, vec![]),
+                Err(ContractError::WrongSs58Version)
+            );
+            // malformed address
+            assert_eq!(
+                contract.decode_ss58_checked("not an address".to_string(), vec![5]),
+                Err(ContractError::FailedToDecode)
+            );
+        }
+
+        #[ink::test]
+        fn test_convert_address_strict_ss58() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let address = AccountId::from([1u8; 32]);
+            let astar_address = convert_address_input(&address, 5);
+            let polkadot_address = convert_address_input(&address, 0);
+
+            // correct prefix
+            assert_eq!(
+                convert_address_output(&astar_address, Some(5), LOG_LEVEL_DEBUG),
+                Ok(address)
+            );
+            // wrong prefix in strict mode
+            assert_eq!(
+                convert_address_output(&polkadot_address, Some(5), LOG_LEVEL_DEBUG),
+                Err(ContractError::WrongSs58Version)
+            );
+            // lenient mode
+            assert_eq!(
+                convert_address_output(&polkadot_address, None, LOG_LEVEL_DEBUG),
+                Ok(address)
+            );
+        }
+
+        #[ink::test]
+        fn test_dry_run_with_ss58_malformed_address() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = init_contract_with_dummy_config();
+            let result = contract.dry_run_with_ss58(
+                4517,
+                2,
+                vec![
+                    "aCG9z4XcZrSUfrzuaUYWwxKruA6rnA8z9wMcZtDQEfPRQLH".to_string(),
+                    "not an address".to_string(),
+                ],
+            );
+            assert_eq!(result, Err(ContractError::FailedToDecode));
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn ping_rpc() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+            assert_eq!(oracle.ping_rpc(), Ok(true));
+
+            // unreachable rpc
+            let oracle = init_contract_with_dummy_config();
+            assert_eq!(oracle.ping_rpc(), Err(ContractError::FailedToCreateClient));
+        }
+
+        #[ink::test]
+        fn ping_rpc_with_mock_client() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            // the anchor is reachable, even if the value is not set
+            let mut client = mock_rollup::MockRollupClient::default();
+            assert_eq!(ping_client(&mut client, LOG_LEVEL_DEBUG), Ok(true));
+            assert_eq!(client.reads(), vec![JsOffchainRollup::NEXT_ERA.encode()]);
+
+            let mut client = mock_rollup::MockRollupClient::default().failing_read();
+            assert_eq!(
+                ping_client(&mut client, LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToCallRollup)
+            );
+        }
+
+        #[ink::test]
+        fn ping_rpc_with_http_error() {
+            use pink_extension::chain_extension::{mock, HttpResponse};
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            mock::mock_http_request(|_| HttpResponse::not_found());
+            let oracle = init_contract_with_dummy_config();
+            assert!(oracle.ping_rpc().is_err());
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn prepare_raffle_reply() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+            let (era, reply) = oracle
+                .prepare_raffle_reply()
+                .expect("failed to prepare the reply");
+            ink::env::debug_println!("era: {era} - reply: {reply:02x?}");
+
+            let response = ResponseMessage::decode(&mut reply.as_slice()).unwrap();
+            assert!(matches!(response, ResponseMessage::JsResponse { .. }));
+        }
+
+        #[ink::test]
+        #[ignore = "The rollup anchor must be reachable"]
+        fn dry_run_excluding() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+            let first_response = oracle
+                .dry_run_excluding(AccountId::from([0u8; 32]))
+                .expect("failed to dry run");
+            for winner in first_response.winners {
+                let response = oracle.dry_run_excluding(winner).expect("failed to dry run");
+                ink::env::debug_println!("answer request: {response:?}");
+                assert!(!response.winners.contains(&winner));
+            }
+        }
+
+        #[ink::test]
+        #[ignore = "The JS Contract is not accessible inner the test"]
+        fn dry_run_with_ss58() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let oracle = init_contract();
+            let r = oracle
+                .dry_run_with_ss58(
+                    4517,
+                    2,
+                    vec![
+                        "aCG9z4XcZrSUfrzuaUYWwxKruA6rnA8z9wMcZtDQEfPRQLH".to_string(),
+                        "aGPdXs8Ke2e9zE57EhMyYAVMC15VEbYBSGmmaVQCcdJkzgK".to_string(),
+                    ],
+                )
+                .expect("failed to dry run");
+            ink::env::debug_println!("answer request: {r:?}");
+        }
+
+        #[ink::test]
+        #[ignore = "The JS Contract is not accessible inner the test"]
+        fn test_dry_run_compare() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            // deterministic script always returning the same winner
+            let winner_script = |winner: &str| {
+                let output = ResponseJs {
+                    era: 4517,
+                    skipped: false,
+                    rewards: 0,
+                    winners: vec![winner.to_string()],
+                    skip_reason: None,
+                    winner_stakes: None,
+                }
+                .encode();
+                alloc::format!("new Uint8Array({output:?})")
+            };
+            let alice = convert_address_input(&accounts.alice, default_ss58_prefix());
+            let bob = convert_address_input(&accounts.bob, default_ss58_prefix());
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js(winner_script(&alice), "{}".to_string())
+                .unwrap();
+
+            let (current, candidate) = contract
+                .dry_run_compare(winner_script(&bob), "{}".to_string(), 4517, 1, vec![])
+                .expect("failed to compare");
+            assert_eq!(current.winners, vec![accounts.alice]);
+            assert_eq!(candidate.winners, vec![accounts.bob]);
+            // nothing is stored
+            assert_eq!(
+                contract.get_core_js().unwrap().script,
+                winner_script(&alice)
+            );
+        }
+
+        #[ink::test]
+        fn test_config_core_js_empty_script() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(
+                contract.config_core_js("".to_string(), "settings".to_string()),
+                Err(ContractError::EmptyCoreJs)
+            );
+            assert_eq!(
+                contract.config_core_js(" \n ".to_string(), "settings".to_string()),
+                Err(ContractError::EmptyCoreJs)
+            );
+            assert!(contract.get_core_js().is_none());
+
+            assert_eq!(
+                contract.config_core_js("script".to_string(), "settings".to_string()),
+                Ok(())
+            );
+            assert_eq!(
+                contract.config_core_js_script("".to_string()),
+                Err(ContractError::EmptyCoreJs)
+            );
+            assert_eq!(contract.get_core_js().unwrap().script, "script");
+        }
+
+        #[ink::test]
+        fn test_get_core_js_hashes() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_core_js_hashes(), None);
+
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+            assert_eq!(
+                contract.get_core_js_hashes(),
+                Some((core_js.code_hash, core_js.settings_hash))
+            );
+        }
+
+        #[ink::test]
+        fn test_compute_response_message_hash() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let output = ResponseJs {
+                era: 4517,
+                skipped: false,
+                rewards: 0,
+                winners: vec![convert_address_input(&accounts.bob, default_ss58_prefix())],
+                skip_reason: None,
+                winner_stakes: None,
+            };
+            mock_js::set_output(Some(output.encode()));
+
+            // the participants and the min participants are read in the anchor, as in run_raffle
+            contract.set_read_participants_from_anchor(true).unwrap();
+            let config_ext = contract.config_ext();
+            let anchor = |min_participants: u32| {
+                mock_rollup::MockRollupClient::default()
+                    .with_value(
+                        JsOffchainRollup::PARTICIPANTS,
+                        vec![(accounts.bob, 100 as Balance), (accounts.charlie, 100)],
+                    )
+                    .with_value(JsOffchainRollup::MIN_PARTICIPANTS, min_participants)
+            };
+            let hash = |contract: &JsOffchainRollup, era: u32, min_participants: u32| {
+                contract.response_message_hash(
+                    &config_ext,
+                    &mut anchor(min_participants),
+                    era,
+                    1,
+                    vec![accounts.alice],
+                )
+            };
+            let expected = hash(&contract, 4517, 0).unwrap();
+            // identical inputs
+            assert_eq!(hash(&contract, 4517, 0), Ok(expected));
+            // another era
+            assert_ne!(hash(&contract, 4518, 0), Ok(expected));
+            // not enough participants: the reply is skipped, as in run_raffle
+            assert_ne!(hash(&contract, 4517, 3), Ok(expected));
+            // the blocklist is applied, as in run_raffle
+            contract.add_to_blocklist(accounts.django).unwrap();
+            assert_ne!(hash(&contract, 4517, 0), Ok(expected));
+            mock_js::set_output(None);
+        }
+
+        #[ink::test]
+        fn test_compute_response_message_hash_not_configured() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let contract = JsOffchainRollup::default();
+            // not restricted to the owner
+            ink::env::test::set_caller::<PinkEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.compute_response_message_hash(4517, 1, vec![]),
+                Err(ContractError::ClientNotConfigured)
+            );
+        }
+
+        #[ink::test]
+        fn test_dry_run_with_parameters_without_config() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            // the client is not required, only the core js
+            let contract = JsOffchainRollup::default();
+            assert_eq!(
+                contract.dry_run_with_parameters(4517, 2, vec![]),
+                Err(ContractError::CoreNotConfigured)
+            );
+        }
+
+        #[ink::test]
+        fn test_dry_run_with_parameters_offline() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let output = ResponseJs {
+                era: 4517,
+                skipped: false,
+                rewards: 0,
+                winners: vec![convert_address_input(&accounts.bob, default_ss58_prefix())],
+                skip_reason: None,
+                winner_stakes: None,
+            }
+            .encode();
+
+            // no config
+            let mut contract = JsOffchainRollup::default();
+            contract
+                .config_core_js("script".to_string(), "{}".to_string())
+                .unwrap();
+            mock_js::set_output(Some(output));
+            let response = contract.dry_run_with_parameters(4517, 1, vec![]);
+            mock_js::set_output(None);
+            let response = response.expect("failed to dry run");
+            let ResponseMessage::JsResponse { output_value, .. } =
+                ResponseMessage::decode(&mut response.as_slice()).unwrap()
+            else {
+                panic!("a js response is expected");
+            };
+            let output = ResponseSc::decode(&mut output_value.as_slice()).unwrap();
+            assert_eq!(output.winners, vec![accounts.bob]);
+        }
+
+        #[ink::test]
+        fn test_handle_request_with_mock_js_output() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+
+            let output_js = ResponseJs {
+                era: 4517,
+                skipped: false,
+                rewards: 163483092786717962675,
+                winners: vec![
+                    convert_address_input(&accounts.bob, default_ss58_prefix()),
+                    convert_address_input(&accounts.charlie, default_ss58_prefix()),
+                ],
+                skip_reason: None,
+                winner_stakes: None,
+            };
+            mock_js::set_output(Some(output_js.encode()));
+
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
+            };
+            let response = contract.build_response(&contract.config_ext(), &request);
+            mock_js::set_output(None);
+
+            let ResponseMessage::JsResponse {
+                js_script_hash,
+                input_hash,
+                settings_hash,
+                output_value,
+            } = response.unwrap()
+            else {
+                panic!("a js response is expected");
+            };
+            assert_eq!(js_script_hash, core_js.code_hash);
+            assert_eq!(settings_hash, core_js.settings_hash);
+            assert_eq!(input_hash, contract.compute_input_hash(&request));
+            assert_eq!(
+                ResponseSc::decode(&mut output_value.as_slice()).unwrap(),
+                ResponseSc {
+                    era: 4517,
+                    skipped: false,
+                    rewards: 163483092786717962675,
+                    winners: vec![accounts.bob, accounts.charlie],
+                    skip_reason: None,
+                    winner_stakes: None,
+                }
+            );
+        }
+
+        /// Mocks the js output and returns the reader with the request of the raffle
+        fn init_mock_raffle(contract: &mut JsOffchainRollup) -> mock_rollup::MockRollupClient {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let output_js = ResponseJs {
+                era: 4517,
+                skipped: false,
+                rewards: 163483092786717962675,
+                winners: vec![convert_address_input(&accounts.bob, default_ss58_prefix())],
+                skip_reason: None,
+                winner_stakes: None,
+            };
+            mock_js::set_output(Some(output_js.encode()));
+
+            mock_rollup::MockRollupClient::default()
+                .with_value(JsOffchainRollup::NEXT_ERA, 4517u32)
+                .with_value(JsOffchainRollup::NB_WINNERS, 1u16)
+                .with_value(JsOffchainRollup::LAST_WINNERS, vec![accounts.alice])
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_separate_reader_and_writer() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let mut reader = init_mock_raffle(&mut contract);
+            let writer = mock_rollup::MockRollupClient::default();
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let result = contract.run_raffle_with_reader(config, &config_ext, &mut reader, |rpc| {
+                assert_eq!(rpc, "http://localhost:9944");
+                Ok(writer.clone())
+            });
+            mock_js::set_output(None);
+            assert_eq!(result, Ok(None));
+
+            // the request is read with the reader only and the reply is committed with the writer only
+            assert_eq!(reader.reads().len(), 4);
+            assert!(reader.commits().is_empty());
+            assert!(writer.reads().is_empty());
+            assert_eq!(writer.commits().len(), 1);
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_reader_commits_with_the_fallback_rpc() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .set_fallback_rpc(Some("http://fallback:9944".to_string()))
+                .unwrap();
+            let mut reader = init_mock_raffle(&mut contract);
+            let primary = mock_rollup::MockRollupClient::default().failing_commit();
+            let fallback = mock_rollup::MockRollupClient::default();
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let result = contract.run_raffle_with_reader(config, &config_ext, &mut reader, |rpc| {
+                Ok(match rpc {
+                    "http://localhost:9944" => primary.clone(),
+                    _ => fallback.clone(),
+                })
+            });
+            mock_js::set_output(None);
+            assert_eq!(result, Ok(None));
+
+            // the request is read once, only the commit is retried
+            assert_eq!(reader.reads().len(), 4);
+            assert!(primary.commits().is_empty());
+            assert_eq!(fallback.commits().len(), 1);
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_reader_without_fallback_rpc() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let mut reader = init_mock_raffle(&mut contract);
+            let writer = mock_rollup::MockRollupClient::default().failing_commit();
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let result = contract
+                .run_raffle_with_reader(config, &config_ext, &mut reader, |_| Ok(writer.clone()));
+            mock_js::set_output(None);
+            assert_eq!(result, Err(ContractError::FailedToCommitTx));
+            assert!(writer.commits().is_empty());
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_client_reads_and_commits_with_the_same_client() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .set_fallback_rpc(Some("http://fallback:9944".to_string()))
+                .unwrap();
+            let primary = init_mock_raffle(&mut contract);
+            let fallback = mock_rollup::MockRollupClient::default();
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let result = contract.run_raffle_with_client(config, &config_ext, |rpc| {
+                Ok(match rpc {
+                    "http://localhost:9944" => primary.clone(),
+                    _ => fallback.clone(),
+                })
+            });
+            mock_js::set_output(None);
+            assert_eq!(result, Ok(None));
+
+            // the fallback rpc is not used when the primary rpc succeeds
+            assert_eq!(primary.reads().len(), 4);
+            assert_eq!(primary.commits().len(), 1);
+            assert!(fallback.reads().is_empty());
+            assert!(fallback.commits().is_empty());
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_client_reads_again_with_the_fallback_rpc() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .set_fallback_rpc(Some("http://fallback:9944".to_string()))
+                .unwrap();
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let fallback = mock_rollup::MockRollupClient::default()
+                .with_value(JsOffchainRollup::NEXT_ERA, 4517u32)
+                .with_value(JsOffchainRollup::NB_WINNERS, 1u16)
+                .with_value(JsOffchainRollup::LAST_WINNERS, vec![accounts.alice]);
+            let primary = init_mock_raffle(&mut contract).failing_commit();
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let result = contract.run_raffle_with_client(config, &config_ext, |rpc| {
+                Ok(match rpc {
+                    "http://localhost:9944" => primary.clone(),
+                    _ => fallback.clone(),
+                })
+            });
+            mock_js::set_output(None);
+            assert_eq!(result, Ok(None));
+
+            // the client of the fallback rpc reads the data before committing the reply
+            assert_eq!(primary.reads().len(), 4);
+            assert!(primary.commits().is_empty());
+            assert_eq!(fallback.reads().len(), 4);
+            assert_eq!(fallback.commits().len(), 1);
+        }
+
+        #[ink::test]
+        fn test_run_raffle_with_client_when_the_primary_rpc_is_down() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let fallback = init_mock_raffle(&mut contract);
+
+            // no fallback rpc: the error is returned
+            let config = contract.ensure_client_configured().unwrap();
+            let result =
+                contract.run_raffle_with_client(config, &contract.config_ext(), |rpc| match rpc {
+                    "http://localhost:9944" => Err(ContractError::FailedToCreateClient),
+                    _ => Ok(fallback.clone()),
+                });
+            assert_eq!(result, Err(ContractError::FailedToCreateClient));
+            assert!(fallback.reads().is_empty());
+
+            contract
+                .set_fallback_rpc(Some("http://fallback:9944".to_string()))
+                .unwrap();
+            let config = contract.ensure_client_configured().unwrap();
+            let mut rpcs = Vec::new();
+            let result = contract.run_raffle_with_client(config, &contract.config_ext(), |rpc| {
+                rpcs.push(rpc.to_string());
+                match rpc {
+                    "http://localhost:9944" => Err(ContractError::FailedToCreateClient),
+                    _ => Ok(fallback.clone()),
+                }
+            });
+            mock_js::set_output(None);
+            assert_eq!(result, Ok(None));
+            assert_eq!(rpcs, vec!["http://localhost:9944", "http://fallback:9944"]);
+            assert_eq!(fallback.reads().len(), 4);
+            assert_eq!(fallback.commits().len(), 1);
+        }
+
+        #[ink::test]
+        fn test_reply_format_version() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
             };
-            return Ok(Some(tx_id));
+            let response = contract
+                .build_error_response(LOG_LEVEL_DEBUG, &request, "NoReward".to_string())
+                .unwrap();
+
+            // implicit version: no version byte
+            assert_eq!(contract.get_reply_format_version(), None);
+            assert_eq!(
+                encode_reply(&contract.config_ext(), &response),
+                response.encode()
+            );
+
+            contract.set_reply_format_version(Some(2)).unwrap();
+            let reply = encode_reply(&contract.config_ext(), &response);
+            assert_eq!(reply[0], 2);
+            assert_eq!(reply[1..], response.encode());
+            assert!(ResponseMessage::decode(&mut &reply[1..]).is_ok());
         }
-        Ok(None)
-    }
 
-    #[derive(Encode, Decode)]
-    pub struct RequestSc {
-        era: u32,
-        nb_winners: u16,
-        excluded: Vec<AccountId>,
-    }
+        #[ink::test]
+        fn test_handle_request_zero_hash() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
 
-    #[derive(Encode, Decode)]
-    pub struct RequestJs {
-        era: u32,
-        nb_winners: u16,
-        excluded: Vec<String>,
-    }
+            let mut contract = init_contract_with_dummy_config();
+            contract.core_js.set(&CoreJs {
+                script: "script".to_string(),
+                settings: "settings".to_string(),
+                code_hash: [0u8; 32],
+                settings_hash: [0u8; 32],
+            });
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![],
+                participants: None,
+                rewards: None,
+            };
+            assert!(matches!(
+                contract.handle_request(&contract.config_ext(), &request),
+                Err(ContractError::CoreNotConfigured)
+            ));
+        }
 
-    fn convert_address_input(address: &AccountId) -> String {
-        let address_hex: [u8; 32] = scale::Encode::encode(&address)
-            .try_into()
-            .expect("incorrect length");
-        AccountId32::from(address_hex)
-            .to_ss58check_with_version(Ss58AddressFormatRegistry::AstarAccount.into())
-    }
+        #[ink::test]
+        fn test_build_error_response() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
 
-    fn convert_request(request_sc: &RequestSc) -> RequestJs {
-        let era = request_sc.era;
-        let nb_winners = request_sc.nb_winners;
-        let excluded = request_sc
-            .excluded
-            .iter()
-            .map(convert_address_input)
-            .collect();
-        RequestJs {
-            era,
-            nb_winners,
-            excluded,
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let core_js = contract.get_core_js().unwrap();
+
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
+            };
+            let response = contract
+                .build_error_response(LOG_LEVEL_DEBUG, &request, "NoReward".to_string())
+                .unwrap();
+
+            let ResponseMessage::Error {
+                js_script_hash,
+                input_value,
+                settings_hash,
+                error,
+            } = response
+            else {
+                panic!("an error response is expected");
+            };
+            assert_eq!(js_script_hash, core_js.code_hash);
+            assert_eq!(settings_hash, core_js.settings_hash);
+            assert_eq!(
+                RequestSc::decode(&mut input_value.as_slice()).unwrap(),
+                request
+            );
+            assert_eq!(error, b"NoReward".to_vec());
+        }
+
+        #[ink::test]
+        fn test_build_response_with_js_error() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            contract.set_max_js_output_bytes(Some(10)).unwrap();
+            let core_js = contract.get_core_js().unwrap();
+
+            // the output of the js exceeds the limit
+            mock_js::set_output(Some(vec![0u8; 11]));
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
+            };
+            let response = contract.build_response(&contract.config_ext(), &request);
+            mock_js::set_output(None);
+
+            // the error is replied to the rollup anchor
+            let ResponseMessage::Error {
+                js_script_hash,
+                input_value,
+                settings_hash,
+                error,
+            } = response.unwrap()
+            else {
+                panic!("an error response is expected");
+            };
+            assert_eq!(js_script_hash, core_js.code_hash);
+            assert_eq!(settings_hash, core_js.settings_hash);
+            assert_eq!(
+                RequestSc::decode(&mut input_value.as_slice()).unwrap(),
+                request
+            );
+            assert_eq!(error, b"output too large".to_vec());
+        }
+
+        #[ink::test]
+        fn test_build_response_with_invalid_js_output() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 2,
+                excluded: vec![accounts.alice],
+                participants: None,
+                rewards: None,
+            };
+
+            // the output of the js cannot be decoded: the error is replied
+            mock_js::set_output(Some(vec![1u8, 2, 3]));
+            let response = contract.build_response(&contract.config_ext(), &request);
+            mock_js::set_output(None);
+            let ResponseMessage::Error { error, .. } = response.unwrap() else {
+                panic!("an error response is expected");
+            };
+            assert_eq!(error, b"FailedToDecode".to_vec());
+
+            // the input of the js is too large: the local guard is not replied
+            contract.set_max_js_input_len(Some(10)).unwrap();
+            let response = contract.build_response(&contract.config_ext(), &request);
+            assert!(matches!(
+                response,
+                Err(ContractError::JsError(JsErrorKind::InputTooLarge, _))
+            ));
+        }
+
+        #[ink::test]
+        fn test_replied_error() {
+            assert_eq!(
+                replied_error(&ContractError::JsError(
+                    JsErrorKind::Runtime,
+                    "Error: no data".to_string()
+                )),
+                Some("Error: no data".to_string())
+            );
+            assert_eq!(
+                replied_error(&ContractError::RewardsOutOfRange),
+                Some("RewardsOutOfRange".to_string())
+            );
+            assert_eq!(
+                replied_error(&ContractError::WrongSs58Version),
+                Some("WrongSs58Version".to_string())
+            );
+            assert_eq!(
+                replied_error(&ContractError::JsError(
+                    JsErrorKind::InputTooLarge,
+                    "input too large".to_string()
+                )),
+                None
+            );
+            assert_eq!(replied_error(&ContractError::CoreNotConfigured), None);
+            assert_eq!(replied_error(&ContractError::FailedToCallRollup), None);
+        }
+
+        #[ink::test]
+        fn test_run_raffle_replies_an_error_once_per_era() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let reader = init_mock_raffle(&mut contract);
+            let writer = mock_rollup::MockRollupClient::default();
+            mock_js::set_output(Some(vec![1u8, 2, 3]));
+
+            let config = contract.ensure_client_configured().unwrap();
+            let config_ext = contract.config_ext();
+            let run = |reader: &mock_rollup::MockRollupClient| {
+                let mut reader = reader.clone();
+                contract.run_raffle_with_reader(config, &config_ext, &mut reader, |_| {
+                    Ok(writer.clone())
+                })
+            };
+            assert_eq!(run(&reader), Ok(None));
+            assert_eq!(writer.commits().len(), 1);
+
+            // the anchor doesn't advance the era on an error: the error is not replied again
+            assert_eq!(run(&reader), Err(ContractError::ErrorAlreadyReplied));
+            assert_eq!(writer.commits().len(), 1);
+
+            // another era
+            let reader = reader.with_value(JsOffchainRollup::NEXT_ERA, 4518u32);
+            assert_eq!(run(&reader), Ok(None));
+            mock_js::set_output(None);
+            assert_eq!(writer.commits().len(), 2);
+        }
+
+        #[ink::test]
+        fn test_minimum_participants() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let request = RequestSc {
+                era: 4517,
+                nb_winners: 1,
+                excluded: vec![],
+                participants: Some(vec![(accounts.alice, 10), (accounts.bob, 20)]),
+                rewards: None,
+            };
+            // disabled
+            assert!(has_minimum_participants(&request, 0, LOG_LEVEL_DEBUG));
+            // at the minimum
+            assert!(has_minimum_participants(&request, 2, LOG_LEVEL_DEBUG));
+            // below the minimum
+            assert!(!has_minimum_participants(&request, 3, LOG_LEVEL_DEBUG));
+
+            let mut contract = init_contract_with_dummy_config();
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            let response = contract
+                .build_skipped_response(
+                    LOG_LEVEL_DEBUG,
+                    &request,
+                    "insufficient participants".to_string(),
+                )
+                .unwrap();
+            let ResponseMessage::JsResponse { output_value, .. } = response else {
+                panic!("a js response is expected");
+            };
+            assert_eq!(
+                ResponseSc::decode(&mut output_value.as_slice()).unwrap(),
+                ResponseSc {
+                    era: 4517,
+                    skipped: true,
+                    rewards: 0,
+                    winners: vec![],
+                    skip_reason: Some("insufficient participants".to_string()),
+                    winner_stakes: None,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn test_is_ready() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = JsOffchainRollup::default();
+            assert_eq!(
+                contract.is_ready(),
+                (
+                    false,
+                    vec![
+                        ContractError::ClientNotConfigured,
+                        ContractError::CoreNotConfigured
+                    ]
+                )
+            );
+
+            contract
+                .config_target_contract(
+                    "http://localhost:9944".to_string(),
+                    70,
+                    6,
+                    [1u8; 32].to_vec(),
+                    None,
+                )
+                .unwrap();
+            assert_eq!(
+                contract.is_ready(),
+                (false, vec![ContractError::CoreNotConfigured])
+            );
+
+            contract
+                .config_core_js("script".to_string(), "settings".to_string())
+                .unwrap();
+            assert_eq!(contract.is_ready(), (true, vec![]));
+
+            contract.config_registry(None, true).unwrap();
+            assert_eq!(
+                contract.is_ready(),
+                (false, vec![ContractError::GraphApiNotConfigured])
+            );
+        }
+
+        #[ink::test]
+        fn test_get_rollup_key_selectors() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            assert_eq!(
+                contract.get_rollup_key_selectors(),
+                (
+                    ink::selector_id!("NEXT_ERA"),
+                    ink::selector_id!("NB_WINNERS"),
+                    ink::selector_id!("LAST_WINNER"),
+                )
+            );
+        }
+
+        #[ink::test]
+        fn test_encode_next_era_value() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let contract = JsOffchainRollup::default();
+            let value = contract.encode_next_era_value(4517);
+            assert_eq!(value, 4517u32.encode());
+            assert_eq!(decode_next_era(Some(value), LOG_LEVEL_DEBUG), Ok(4517));
+        }
+
+        #[ink::test]
+        fn test_check_js_input_len() {
+            let script = "x".repeat(100);
+            let args = vec!["0x01".to_string(), "settings".to_string()];
+
+            // no limit
+            assert_eq!(
+                check_js_input_len(&script, &args, None, LOG_LEVEL_DEBUG),
+                Ok(())
+            );
+            // under the limit
+            assert_eq!(
+                check_js_input_len(&script, &args, Some(112), LOG_LEVEL_DEBUG),
+                Ok(())
+            );
+            // over the limit
+            assert_eq!(
+                check_js_input_len(&script, &args, Some(111), LOG_LEVEL_DEBUG),
+                Err(ContractError::JsError(
+                    JsErrorKind::InputTooLarge,
+                    "input too large".to_string()
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn test_check_js_output_len() {
+            let output = vec![0u8; 100];
+
+            // no limit
+            assert_eq!(check_js_output_len(&output, None, LOG_LEVEL_DEBUG), Ok(()));
+            // under the limit
+            assert_eq!(
+                check_js_output_len(&output, Some(100), LOG_LEVEL_DEBUG),
+                Ok(())
+            );
+            // over the limit
+            assert_eq!(
+                check_js_output_len(&output, Some(99), LOG_LEVEL_DEBUG),
+                Err(ContractError::JsError(
+                    JsErrorKind::BadOutput,
+                    "output too large".to_string()
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn test_eval_js_input_too_large() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            contract.set_allow_eval_js(true).unwrap();
+            contract.set_max_js_input_len(Some(10)).unwrap();
+
+            // the guard fails before evaluating the js
+            let result = contract.eval_js("while (true) {}".to_string(), vec![]);
+            assert_eq!(
+                result,
+                Err(ContractError::JsError(
+                    JsErrorKind::InputTooLarge,
+                    "input too large".to_string()
+                ))
+            );
+        }
+
+        #[ink::test]
+        fn test_decode_nb_winners() {
+            assert_eq!(decode_nb_winners(&3u16.encode(), LOG_LEVEL_DEBUG), Ok(3));
+            assert_eq!(
+                decode_nb_winners(&u16::MAX.encode(), LOG_LEVEL_DEBUG),
+                Ok(u16::MAX)
+            );
+            // the anchor stores a u16, a wider integer is rejected even if it is in the range
+            assert_eq!(
+                decode_nb_winners(&5u32.encode(), LOG_LEVEL_DEBUG),
+                Err(ContractError::NbWinnersOutOfRange)
+            );
+            assert_eq!(
+                decode_nb_winners(&(u16::MAX as u32 + 1).encode(), LOG_LEVEL_DEBUG),
+                Err(ContractError::NbWinnersOutOfRange)
+            );
+            assert_eq!(
+                decode_nb_winners(&u64::MAX.encode(), LOG_LEVEL_DEBUG),
+                Err(ContractError::NbWinnersOutOfRange)
+            );
+            // malformed value
+            assert_eq!(
+                decode_nb_winners(&[1], LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToDecode)
+            );
+            assert_eq!(
+                decode_nb_winners(&[], LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToDecode)
+            );
+        }
+
+        #[ink::test]
+        fn test_log_level() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            let emit_logs = |contract: &JsOffchainRollup| {
+                mock_log::take();
+                contract.log_error(format_args!("error"));
+                contract.log_info(format_args!("info"));
+                contract.log_debug(format_args!("debug"));
+                // logged by a helper, with the error of the result
+                let _ = contract.decode_ss58_checked("not an address".to_string(), vec![]);
+                mock_log::take()
+            };
+
+            // everything is logged by default
+            assert_eq!(contract.get_log_level(), LOG_LEVEL_DEBUG);
+            assert_eq!(
+                emit_logs(&contract),
+                vec![
+                    LOG_LEVEL_ERROR,
+                    LOG_LEVEL_INFO,
+                    LOG_LEVEL_DEBUG,
+                    LOG_LEVEL_ERROR
+                ]
+            );
+
+            contract.set_log_level(Some(0)).unwrap();
+            assert_eq!(emit_logs(&contract), vec![]);
+
+            contract.set_log_level(Some(LOG_LEVEL_ERROR)).unwrap();
+            assert_eq!(emit_logs(&contract), vec![LOG_LEVEL_ERROR, LOG_LEVEL_ERROR]);
+
+            contract.set_log_level(Some(LOG_LEVEL_INFO)).unwrap();
+            assert_eq!(
+                emit_logs(&contract),
+                vec![LOG_LEVEL_ERROR, LOG_LEVEL_INFO, LOG_LEVEL_ERROR]
+            );
         }
-    }
 
-    #[derive(scale::Encode, scale::Decode)]
-    pub struct ResponseJs {
-        pub era: u32,
-        pub skipped: bool,
-        pub rewards: Balance,
-        pub winners: Vec<String>,
-    }
+        #[ink::test]
+        fn test_log_level_in_helpers() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
 
-    #[derive(scale::Encode, scale::Decode)]
-    pub struct ResponseSc {
-        pub era: u32,
-        pub skipped: bool,
-        pub rewards: Balance,
-        pub winners: Vec<AccountId>,
-    }
+            mock_log::take();
+            assert!(decode_next_era(Some(vec![1, 2, 3]), 0).is_err());
+            assert!(resolve_nb_winners(None, None, 0).is_err());
+            assert!(check_js_output_len(&[0u8; 10], Some(1), 0).is_err());
+            let config = ConfigExt {
+                log_level: Some(0),
+                ..Default::default()
+            };
+            assert!(try_convert_output(vec![1, 2, 3], &config).is_err());
+            assert_eq!(mock_log::take(), vec![]);
 
-    fn convert_address_output(address: &str) -> AccountId {
-        let account_id = AccountId32::from_ss58check(address).expect("incorrect address");
-        let address_hex: [u8; 32] = scale::Encode::encode(&account_id)
-            .try_into()
-            .expect("incorrect length");
-        AccountId::from(address_hex)
-    }
+            assert!(decode_next_era(Some(vec![1, 2, 3]), LOG_LEVEL_ERROR).is_err());
+            assert!(decode_next_era(None, LOG_LEVEL_ERROR).is_err());
+            assert_eq!(mock_log::take(), vec![LOG_LEVEL_ERROR]);
+            assert!(decode_next_era(None, LOG_LEVEL_INFO).is_err());
+            assert_eq!(mock_log::take(), vec![LOG_LEVEL_INFO]);
+        }
 
-    fn convert_output(output: Vec<u8>) -> Vec<u8> {
-        let output_js =
-            ResponseJs::decode(&mut output.as_slice()).expect("failed to convert js output");
-        let era = output_js.era;
-        let skipped = output_js.skipped;
-        let rewards = output_js.rewards;
-        let winners = output_js
-            .winners
-            .iter()
-            .map(|s| convert_address_output(s.as_str()))
-            .collect();
-        let output_sc = ResponseSc {
-            era,
-            skipped,
-            rewards,
-            winners,
-        };
+        #[ink::test]
+        fn test_get_display_settings() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
 
-        output_sc.encode()
-    }
+            // defaults
+            let contract = JsOffchainRollup::default();
+            assert_eq!(contract.get_display_settings(), (5, 18));
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_display_settings(), (5, 18));
 
-        struct EnvVars {
-            /// The RPC endpoint of the target blockchain
-            rpc: String,
-            pallet_id: u8,
-            call_id: u8,
-            /// The rollup anchor address on the target blockchain
-            contract_id: ContractId,
-            /// When we want to manually set the attestor key for signing the message (only dev purpose)
-            attest_key: Vec<u8>,
-            /// When we want to use meta tx
-            sender_key: Option<Vec<u8>>,
+            // overrides
+            contract.config_ss58(Some(42), false).unwrap();
+            contract.set_token_decimals(Some(12)).unwrap();
+            assert_eq!(contract.get_display_settings(), (42, 12));
         }
 
-        fn get_env(key: &str) -> String {
-            std::env::var(key).expect("env not found")
+        #[ink::test]
+        fn test_format_rewards() {
+            let _ = env_logger::try_init();
+            pink_extension_runtime::mock_ext::mock_all_ext();
+
+            let mut contract = init_contract_with_dummy_config();
+            assert_eq!(contract.get_token_decimals(), 18);
+
+            // whole amounts
+            assert_eq!(contract.format_rewards(1_000_000_000_000_000_000), "1");
+            assert_eq!(contract.format_rewards(25_000_000_000_000_000_000), "25");
+            // fractional amounts
+            assert_eq!(contract.format_rewards(1_500_000_000_000_000_000), "1.5");
+            assert_eq!(
+                contract.format_rewards(163483092786717962675),
+                "163.483092786717962675"
+            );
+            assert_eq!(contract.format_rewards(1), "0.000000000000000001");
+            // zero
+            assert_eq!(contract.format_rewards(0), "0");
+
+            contract.set_token_decimals(Some(0)).unwrap();
+            assert_eq!(contract.format_rewards(1234), "1234");
+            contract.set_token_decimals(Some(2)).unwrap();
+            assert_eq!(contract.format_rewards(1234), "12.34");
+            assert_eq!(contract.format_rewards(5), "0.05");
         }
 
-        fn config() -> EnvVars {
-            dotenvy::dotenv().ok();
-            let rpc = get_env("RPC");
-            let pallet_id: u8 = get_env("PALLET_ID").parse().expect("u8 expected");
-            let call_id: u8 = get_env("CALL_ID").parse().expect("u8 expected");
-            let contract_id: ContractId = hex::decode(get_env("CONTRACT_ID"))
-                .expect("hex decode failed")
-                .try_into()
-                .expect("incorrect length");
-            let attest_key = hex::decode(get_env("ATTEST_KEY")).expect("hex decode failed");
-            let sender_key = std::env::var("SENDER_KEY")
-                .map(|s| hex::decode(s).expect("hex decode failed"))
-                .ok();
+        #[ink::test]
+        fn test_classify_js_error() {
+            assert_eq!(
+                classify_js_error("SyntaxError: unexpected token in expression: ')'"),
+                JsErrorKind::Syntax
+            );
+            assert_eq!(
+                classify_js_error("Error: no rewards for era 4517\n    at <eval>:1"),
+                JsErrorKind::Runtime
+            );
+            assert_eq!(
+                classify_js_error("TypeError: cannot read property 'winners' of undefined"),
+                JsErrorKind::Runtime
+            );
+            assert_eq!(
+                classify_js_error("InternalError: interrupted"),
+                JsErrorKind::Timeout
+            );
+        }
 
-            EnvVars {
-                rpc: rpc.to_string(),
-                pallet_id,
-                call_id,
-                contract_id: contract_id.into(),
-                attest_key,
-                sender_key,
-            }
+        #[ink::test]
+        fn test_classify_js_error_message_not_inspected() {
+            // runtime errors whose message looks like another category
+            assert_eq!(
+                classify_js_error("Error: timeout when fetching the participants"),
+                JsErrorKind::Runtime
+            );
+            assert_eq!(
+                classify_js_error("Error: the request timed out"),
+                JsErrorKind::Runtime
+            );
+            assert_eq!(
+                classify_js_error("TypeError: SyntaxError is not a constructor"),
+                JsErrorKind::Runtime
+            );
         }
 
-        fn init_contract() -> JsOffchainRollup {
-            let EnvVars {
-                rpc,
-                pallet_id,
-                call_id,
-                contract_id,
-                attest_key,
-                sender_key,
-            } = config();
+        #[ink::test]
+        fn test_decode_next_era() {
+            assert_eq!(
+                decode_next_era(Some(4517u32.encode()), LOG_LEVEL_DEBUG),
+                Ok(4517)
+            );
+            // key absent
+            assert_eq!(
+                decode_next_era(None, LOG_LEVEL_DEBUG),
+                Err(ContractError::NextEraUnknown)
+            );
+            // key present but malformed
+            assert_eq!(
+                decode_next_era(Some(vec![1, 2, 3]), LOG_LEVEL_DEBUG),
+                Err(ContractError::NextEraDecodeFailed)
+            );
+            assert_eq!(
+                decode_next_era(Some(4517u64.encode()), LOG_LEVEL_DEBUG),
+                Err(ContractError::NextEraDecodeFailed)
+            );
+        }
 
-            let mut oracle = JsOffchainRollup::default();
-            oracle
-                .config_target_contract(rpc, pallet_id, call_id, contract_id.into(), sender_key)
-                .unwrap();
-            //oracle.set_attest_key(Some(attest_key)).unwrap();
+        #[ink::test]
+        fn test_decode_rollup_value() {
+            let raw = 4517u32.encode();
+            // the raw value is not a SCALE-encoded Vec<u8>
+            assert!(Vec::<u8>::decode(&mut raw.as_slice()).map_or(true, |v| v != raw));
+            assert_eq!(
+                decode_rollup_value::<u32>(Some(raw), LOG_LEVEL_DEBUG),
+                Ok(Some(4517))
+            );
+            // key absent
+            assert_eq!(decode_rollup_value::<u32>(None, LOG_LEVEL_DEBUG), Ok(None));
+            // key present but malformed
+            assert_eq!(
+                decode_rollup_value::<u32>(Some(vec![1, 2]), LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToDecode)
+            );
+            assert_eq!(
+                decode_rollup_value::<u32>(Some(4517u64.encode()), LOG_LEVEL_DEBUG),
+                Err(ContractError::FailedToDecode)
+            );
+        }
 
-            oracle
+        #[ink::test]
+        fn test_decode_last_winners() {
+            let accounts = ink::env::test::default_accounts::<pink_extension::PinkEnvironment>();
+            let last_winners = vec![accounts.alice, accounts.bob];
+            assert_eq!(
+                decode_last_winners(Some(last_winners.encode()), LOG_LEVEL_DEBUG),
+                Ok(last_winners)
+            );
+            // key absent
+            assert_eq!(decode_last_winners(None, LOG_LEVEL_DEBUG), Ok(vec![]));
+            // key present but malformed
+            assert_eq!(
+                decode_last_winners(Some(vec![8, 1, 2, 3]), LOG_LEVEL_DEBUG),
+                Err(ContractError::ExcludedDecodeFailed)
+            );
+            assert_eq!(
+                decode_last_winners(Some(4517u32.encode()), LOG_LEVEL_DEBUG),
+                Err(ContractError::ExcludedDecodeFailed)
+            );
         }
 
         #[ink::test]
-        #[ignore = "The JS Contract is not accessible inner the test"]
-        fn run_raffle() {
-            let _ = env_logger::try_init();
-            pink_extension_runtime::mock_ext::mock_all_ext();
+        fn test_resolve_nb_winners() {
+            // key present: the default value is ignored
+            assert_eq!(
+                resolve_nb_winners(Some(3u16.encode()), None, LOG_LEVEL_DEBUG),
+                Ok(3)
+            );
+            assert_eq!(
+                resolve_nb_winners(Some(3u16.encode()), Some(5), LOG_LEVEL_DEBUG),
+                Ok(3)
+            );
+            // key absent with a default value
+            assert_eq!(resolve_nb_winners(None, Some(5), LOG_LEVEL_DEBUG), Ok(5));
+            // key absent without default value
+            assert_eq!(
+                resolve_nb_winners(None, None, LOG_LEVEL_DEBUG),
+                Err(ContractError::NbWinnersNotSet)
+            );
+        }
 
-            let oracle = init_contract();
+        #[ink::test]
+        fn test_split_rewards_equal() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let winners = vec![
+                (accounts.alice, 10),
+                (accounts.bob, 20),
+                (accounts.charlie, 70),
+            ];
 
-            let r = oracle.run_raffle().expect("failed to run raffle");
-            ink::env::debug_println!("answer request: {r:?}");
+            // the remainder is given to the first winner
+            let shares = split_rewards(&winners, 1_000, SplitMode::Equal).unwrap();
+            assert_eq!(
+                shares,
+                vec![
+                    (accounts.alice, 334),
+                    (accounts.bob, 333),
+                    (accounts.charlie, 333)
+                ]
+            );
+
+            assert_eq!(split_rewards(&[], 1_000, SplitMode::Equal).unwrap(), vec![]);
         }
 
         #[ink::test]
-        fn test_convert_address() {
-            let _ = env_logger::try_init();
-            pink_extension_runtime::mock_ext::mock_all_ext();
+        fn test_split_rewards_proportional() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            let winners = vec![
+                (accounts.alice, 10),
+                (accounts.bob, 20),
+                (accounts.charlie, 70),
+            ];
 
-            let address_hex: [u8; 32] =
-                hex::decode("bc5a6b58324a633175374b57464a42357476554b3364774e4673454132436e66")
-                    .expect("hex decode failed")
-                    .try_into()
-                    .expect("incorrect length");
-            let address = AccountId::from(address_hex);
+            let shares = split_rewards(&winners, 1_000, SplitMode::Proportional).unwrap();
+            assert_eq!(
+                shares,
+                vec![
+                    (accounts.alice, 100),
+                    (accounts.bob, 200),
+                    (accounts.charlie, 700)
+                ]
+            );
 
-            let astar_address_str = convert_address_input(&address);
+            // the remainder is given to the first winner
+            let winners = vec![
+                (accounts.alice, 1),
+                (accounts.bob, 1),
+                (accounts.charlie, 1),
+            ];
+            let shares = split_rewards(&winners, 100, SplitMode::Proportional).unwrap();
             assert_eq!(
-                astar_address_str,
-                "aCG9z4XcZrSUfrzuaUYWwxKruA6rnA8z9wMcZtDQEfPRQLH"
+                shares,
+                vec![
+                    (accounts.alice, 34),
+                    (accounts.bob, 33),
+                    (accounts.charlie, 33)
+                ]
+            );
+
+            // no stake: the rewards are split equally
+            let winners = vec![(accounts.alice, 0), (accounts.bob, 0)];
+            let shares = split_rewards(&winners, 100, SplitMode::Proportional).unwrap();
+            assert_eq!(shares, vec![(accounts.alice, 50), (accounts.bob, 50)]);
+
+            // big values don't overflow
+            let winners = vec![
+                (accounts.alice, u128::MAX / 2),
+                (accounts.bob, u128::MAX / 2),
+            ];
+            let shares = split_rewards(&winners, u128::MAX - 1, SplitMode::Proportional).unwrap();
+            assert_eq!(
+                shares,
+                vec![
+                    (accounts.alice, u128::MAX / 2),
+                    (accounts.bob, u128::MAX / 2)
+                ]
             );
+        }
 
-            assert_eq!(address, convert_address_output(&astar_address_str));
+        #[ink::test]
+        fn test_split_rewards_stake_overflow() {
+            let accounts = ink::env::test::default_accounts::<PinkEnvironment>();
+            // the stakes sum past u128::MAX
+            let winners = vec![(accounts.alice, u128::MAX), (accounts.bob, 1)];
+            assert_eq!(
+                split_rewards(&winners, 100, SplitMode::Proportional),
+                Err(ContractError::StakeOverflow)
+            );
+            assert_eq!(
+                split_rewards(&winners, 100, SplitMode::Equal),
+                Err(ContractError::StakeOverflow)
+            );
         }
 
         #[ink::test]
@@ -662,8 +5669,10 @@ mod lucky_raffle {
                 era,
                 nb_winners,
                 excluded,
+                participants: None,
+                rewards: None,
             };
-            let request_js = convert_request(&request_sc);
+            let request_js = convert_request(&request_sc, default_ss58_prefix());
             let encoded_request = scale::Encode::encode(&request_js);
             ink::env::debug_println!("encoded request: {encoded_request:02x?}");
         }
@@ -680,9 +5689,229 @@ mod lucky_raffle {
             skipped: false,
             rewards: 163483092786717962675,
             winners: vec![address_string],
+            skip_reason: None,
+            winner_stakes: None,
         };
 
-        let response = convert_output(response_sc.encode());
+        let response = try_convert_output(response_sc.encode(), &ConfigExt::default()).unwrap();
         ink::env::debug_println!("output: {response:02x?}");
     }
+
+    #[ink::test]
+    fn test_convert_output_rewards() {
+        let _ = env_logger::try_init();
+        pink_extension_runtime::mock_ext::mock_all_ext();
+
+        let address_string = "aGPdXs8Ke2e9zE57EhMyYAVMC15VEbYBSGmmaVQCcdJkzgK".to_string();
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: false,
+            rewards: 1_000,
+            winners: vec![address_string],
+            skip_reason: None,
+            winner_stakes: None,
+        };
+
+        // normal value
+        let config = ConfigExt {
+            max_rewards: Some(1_000),
+            ..Default::default()
+        };
+        let response = try_convert_output(response_js.encode(), &config).unwrap();
+        let response_sc = ResponseSc::decode(&mut response.as_slice()).unwrap();
+        assert_eq!(response_sc.rewards, 1_000);
+
+        // value above the ceiling
+        let config = ConfigExt {
+            max_rewards: Some(999),
+            ..Default::default()
+        };
+        let result = try_convert_output(response_js.encode(), &config);
+        assert!(matches!(result, Err(ContractError::RewardsOutOfRange)));
+
+        // malformed encoding: the output is truncated
+        let mut encoded = response_js.encode();
+        let result = try_convert_output(encoded[..10].to_vec(), &ConfigExt::default());
+        assert!(matches!(result, Err(ContractError::FailedToDecode)));
+
+        // malformed encoding: unexpected bytes after the output
+        encoded.extend_from_slice(&[0, 0, 0, 0]);
+        let result = try_convert_output(encoded, &ConfigExt::default());
+        assert!(matches!(result, Err(ContractError::FailedToDecode)));
+    }
+
+    #[ink::test]
+    fn test_convert_output_require_rewards() {
+        let _ = env_logger::try_init();
+        pink_extension_runtime::mock_ext::mock_all_ext();
+
+        let address_string = "aGPdXs8Ke2e9zE57EhMyYAVMC15VEbYBSGmmaVQCcdJkzgK".to_string();
+        let config = ConfigExt {
+            require_rewards: true,
+            ..Default::default()
+        };
+
+        // rewards present
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: false,
+            rewards: 1_000,
+            winners: vec![address_string.clone()],
+            skip_reason: None,
+            winner_stakes: None,
+        };
+        assert!(try_convert_output(response_js.encode(), &config).is_ok());
+
+        // rewards absent
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: false,
+            rewards: 0,
+            winners: vec![address_string.clone()],
+            skip_reason: None,
+            winner_stakes: None,
+        };
+        let result = try_convert_output(response_js.encode(), &config);
+        assert_eq!(result, Err(ContractError::RewardsNotSet));
+        // no check when the flag is off
+        assert!(try_convert_output(response_js.encode(), &ConfigExt::default()).is_ok());
+
+        // no rewards expected when the raffle is skipped
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: true,
+            rewards: 0,
+            winners: vec![],
+            skip_reason: None,
+            winner_stakes: None,
+        };
+        assert!(try_convert_output(response_js.encode(), &config).is_ok());
+    }
+
+    #[ink::test]
+    fn test_convert_output_skip_reason() {
+        let _ = env_logger::try_init();
+        pink_extension_runtime::mock_ext::mock_all_ext();
+
+        // skipped with a reason
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: true,
+            rewards: 0,
+            winners: vec![],
+            skip_reason: Some("Voting subPeriod".to_string()),
+            winner_stakes: None,
+        };
+        let response = try_convert_output(response_js.encode(), &ConfigExt::default()).unwrap();
+        let response_sc = ResponseSc::decode(&mut response.as_slice()).unwrap();
+        assert!(response_sc.skipped);
+        assert_eq!(
+            response_sc.skip_reason,
+            Some("Voting subPeriod".to_string())
+        );
+
+        // skipped without reason
+        let response_js = ResponseJs {
+            era: 4589,
+            skipped: true,
+            rewards: 0,
+            winners: vec![],
+            skip_reason: None,
+            winner_stakes: None,
+        };
+        let mut encoded = response_js.encode();
+        let response = try_convert_output(encoded.clone(), &ConfigExt::default()).unwrap();
+        let response_sc = ResponseSc::decode(&mut response.as_slice()).unwrap();
+        assert!(response_sc.skipped);
+        assert_eq!(response_sc.skip_reason, None);
+
+        // the js must provide the optional fields
+        encoded.pop();
+        encoded.pop();
+        assert_eq!(
+            try_convert_output(encoded, &ConfigExt::default()),
+            Err(ContractError::FailedToDecode)
+        );
+    }
+
+    #[ink::test]
+    fn test_convert_output_sort_winners() {
+        let _ = env_logger::try_init();
+        pink_extension_runtime::mock_ext::mock_all_ext();
+
+        let accounts = ink::env::test::default_accounts::<pink_extension::PinkEnvironment>();
+        let prefix = default_ss58_prefix();
+        let response_js = |winners: Vec<AccountId>| ResponseJs {
+            era: 4589,
+            skipped: false,
+            rewards: 100,
+            winners: winners
+                .iter()
+                .map(|w| convert_address_input(w, prefix))
+                .collect(),
+            skip_reason: None,
+            winner_stakes: None,
+        };
+        let output_1 = response_js(vec![accounts.charlie, accounts.alice, accounts.bob]).encode();
+        let output_2 = response_js(vec![accounts.bob, accounts.charlie, accounts.alice]).encode();
+
+        // js order by default
+        let response = try_convert_output(output_1.clone(), &ConfigExt::default()).unwrap();
+        let response_sc = ResponseSc::decode(&mut response.as_slice()).unwrap();
+        assert_eq!(
+            response_sc.winners,
+            vec![accounts.charlie, accounts.alice, accounts.bob]
+        );
+
+        let config = ConfigExt {
+            sort_winners: true,
+            ..Default::default()
+        };
+        let response_1 = try_convert_output(output_1, &config).unwrap();
+        let response_2 = try_convert_output(output_2, &config).unwrap();
+        assert_eq!(response_1, response_2);
+        let response_sc = ResponseSc::decode(&mut response_1.as_slice()).unwrap();
+        assert_eq!(
+            response_sc.winners,
+            vec![accounts.alice, accounts.bob, accounts.charlie]
+        );
+    }
+
+    #[ink::test]
+    fn test_convert_output_winner_stakes() {
+        let _ = env_logger::try_init();
+        pink_extension_runtime::mock_ext::mock_all_ext();
+
+        let accounts = ink::env::test::default_accounts::<pink_extension::PinkEnvironment>();
+        let prefix = default_ss58_prefix();
+        let response_js = |winner_stakes: Option<Vec<Balance>>| ResponseJs {
+            era: 4589,
+            skipped: false,
+            rewards: 100,
+            winners: vec![
+                convert_address_input(&accounts.charlie, prefix),
+                convert_address_input(&accounts.alice, prefix),
+            ],
+            skip_reason: None,
+            winner_stakes,
+        };
+
+        // the stakes stay aligned with the winners when they are sorted
+        let config = ConfigExt {
+            sort_winners: true,
+            ..Default::default()
+        };
+        let output = response_js(Some(vec![300, 100])).encode();
+        let response = try_convert_output(output, &config).unwrap();
+        let response_sc = ResponseSc::decode(&mut response.as_slice()).unwrap();
+        assert_eq!(response_sc.winners, vec![accounts.alice, accounts.charlie]);
+        assert_eq!(response_sc.winner_stakes, Some(vec![100, 300]));
+
+        // one stake per winner
+        let output = response_js(Some(vec![300])).encode();
+        assert_eq!(
+            try_convert_output(output, &config),
+            Err(ContractError::FailedToDecode)
+        );
+    }
 }
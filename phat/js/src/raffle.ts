@@ -4,17 +4,23 @@
 // *** WITH THE PHALA TEAM AT https://discord.gg/5HfmWQNX THANK YOU             ***
 import "@phala/pink-env";
 import {
+    createOptionDecoder,
+    createOptionEncoder,
     createStructDecoder,
     createStructEncoder,
+    createTupleDecoder,
     createVecDecoder,
     createVecEncoder,
     decodeStr,
     decodeU16,
     decodeU32,
+    decodeU128,
     encodeBool,
     encodeStr,
     encodeU128,
     encodeU32,
+    Enum,
+    Option,
     WalkerImpl,
 } from "@scale-codec/core";
 
@@ -24,12 +30,18 @@ type Input = {
     era: number,
     nbWinners: number,
     excluded: string[],
+    // participants (address, stake) read by the phat contract in the rollup anchor
+    participants: Option<[string, bigint][]>,
+    // rewards read by the phat contract in the rollup anchor, as a decimal string
+    rewards: Option<string>,
 }
 
 const decodeInput = createStructDecoder<Input>([
     ['era', decodeU32],
     ['nbWinners', decodeU16],
     ['excluded', createVecDecoder(decodeStr)],
+    ['participants', createOptionDecoder(createVecDecoder(createTupleDecoder<[string, bigint]>([decodeStr, decodeU128])))],
+    ['rewards', createOptionDecoder(decodeStr)],
 ]);
 
 
@@ -38,6 +50,10 @@ type Output = {
     skipped: boolean,
     rewards: bigint,
     winners: string[],
+    // why the raffle has been skipped
+    skipReason: Option<string>,
+    // stakes of the winners, in the same order
+    winnerStakes: Option<bigint[]>,
 }
 
 const encodeOutput = createStructEncoder<Output>([
@@ -45,6 +61,8 @@ const encodeOutput = createStructEncoder<Output>([
     ['skipped', encodeBool],
     ['rewards', encodeU128],
     ['winners', createVecEncoder(encodeStr)],
+    ['skipReason', createOptionEncoder(encodeStr)],
+    ['winnerStakes', createOptionEncoder(createVecEncoder(encodeU128))],
 ]);
 
 enum Error {
@@ -240,9 +258,17 @@ function getRewards(graphApi: string, era: number): bigint {
 
 export type Participant = {
     address: string;
+    stake: bigint;
     nbTickets: number;
 }
 
+const ticketPrice = BigInt(500000000000000);
+
+function toParticipant(address: string, stake: bigint): Participant {
+    const nbTickets = stake / ticketPrice;
+    return {address, stake, nbTickets: Number(nbTickets)};
+}
+
 interface GetParticipantsQueryResult {
     sum: { amount: string };
     keys: string[];
@@ -292,16 +318,13 @@ function getParticipants(graphApi: string, period: number, era: number): Partici
     let participants: Participant[] = [];
     const participantsQueryResult: Array<GetParticipantsQueryResult> = JSON.parse(respBody).data.stakes.groupedAggregates;
 
-    const ticketPrice = BigInt(500000000000000);
-
     for (let i = 0; i < participantsQueryResult.length; i++) {
 
         const address = participantsQueryResult[i].keys[0];
         const stake = participantsQueryResult[i].sum;
         const stakeBigInt = BigInt(parseFloat(stake.amount));
-        const nbTickets = stakeBigInt / ticketPrice;
 
-        participants.push({address, nbTickets: Number(nbTickets)});
+        participants.push(toParticipant(address, stakeBigInt));
     }
 
     console.log(`Number of participants: ${participants.length}`);
@@ -316,7 +339,7 @@ function excludeParticipants(participants: Participant[], excluded: string[]): P
     return participants.filter((p, i) => !excluded.includes(p.address));
 }
 
-function selectWinner(participants: Participant[]): string {
+function selectWinner(participants: Participant[]): Participant {
 
     let totalTickets = 0;
 
@@ -334,13 +357,13 @@ function selectWinner(participants: Participant[]): string {
     for (let i = 0; i < participants.length; i++) {
         currentTicket += participants[i].nbTickets;
         if (currentTicket >= winnerTicket) {
-            return participants[i].address;
+            return participants[i];
         }
     }
     throw Error.NoWinnerFound;
 }
 
-function parseInput(hexx: string): Input {
+export function parseInput(hexx: string): Input {
     let hex = hexx.toString();
     if (!isHexString(hex)) {
         throw Error.FailedToDecodeInput;
@@ -358,7 +381,7 @@ function parseInput(hexx: string): Input {
     return WalkerImpl.decode(new Uint8Array(arr), decodeInput);
 }
 
-function formatOutput(output: Output): Uint8Array {
+export function formatOutput(output: Output): Uint8Array {
     return WalkerImpl.encode(output, encodeOutput);
 }
 
@@ -370,7 +393,8 @@ function formatOutput(output: Output): Uint8Array {
 // The function will be called with two parameters:
 //
 // - request: The raw payload from the contract call `request`.
-//            In this example, it's a struct with the dAppId: { dappId }
+//            In this example, it's a struct with the era, the number of winners, the excluded addresses
+//            and optionally the participants and the rewards read in the rollup anchor.
 // - settings: The custom settings you set with the `config_core` function of the Action Offchain Rollup Phat Contract.
 //            In this example, it's just a simple text of the graph api url.
 //
@@ -382,6 +406,8 @@ export default function main(request: HexString, settings: string): Uint8Array {
     const era = input.era;
     const nbWinners = input.nbWinners;
     const excluded = input.excluded;
+    const participantsFromAnchor = input.participants.tag == 'Some' ? input.participants.as('Some') : undefined;
+    const rewardsFromAnchor = input.rewards.tag == 'Some' ? input.rewards.as('Some') : undefined;
 
     console.log(`Era ${era} - select ${nbWinners} address(es) excluding [${excluded}]`);
     console.log(`Settings: ${settings}`);
@@ -397,6 +423,8 @@ export default function main(request: HexString, settings: string): Uint8Array {
                 skipped: true,
                 rewards: BigInt(0),
                 winners: [],
+                skipReason: Enum.variant('Some', 'voting sub-period'),
+                winnerStakes: Enum.variant('None'),
             }
 
             console.log(`Voting subPeriod for era: ${output.era} => skip the raffle`);
@@ -406,16 +434,21 @@ export default function main(request: HexString, settings: string): Uint8Array {
 
         const period = eraInfo.period;
 
-        const rewards = getRewards(graphApi, era);
-        let participants = getParticipants(graphApi, period, era);
+        // the values read in the rollup anchor are used instead of querying the indexer
+        const rewards = rewardsFromAnchor != undefined ? BigInt(rewardsFromAnchor) : getRewards(graphApi, era);
+        let participants = participantsFromAnchor != undefined
+            ? participantsFromAnchor.map(([address, stake]) => toParticipant(address, stake))
+            : getParticipants(graphApi, period, era);
         participants = excludeParticipants(participants, excluded);
 
         let winners: string[] = [];
+        let winnerStakes: bigint[] = [];
 
         for (let i = 0; i < nbWinners; i++) {
             const winner = selectWinner(participants);
-            winners.push(winner);
-            participants = excludeParticipants(participants, [winner]);
+            winners.push(winner.address);
+            winnerStakes.push(winner.stake);
+            participants = excludeParticipants(participants, [winner.address]);
         }
 
         const output: Output = {
@@ -423,6 +456,8 @@ export default function main(request: HexString, settings: string): Uint8Array {
             skipped: false,
             rewards: BigInt(rewards.valueOf()),
             winners,
+            skipReason: Enum.variant('None'),
+            winnerStakes: Enum.variant('Some', winnerStakes),
         }
 
         console.log(`winners: ${output.winners}`);
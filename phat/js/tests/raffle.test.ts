@@ -0,0 +1,163 @@
+import { test } from "node:test";
+import assert from "node:assert/strict";
+import {
+    createOptionDecoder,
+    createOptionEncoder,
+    createStructDecoder,
+    createStructEncoder,
+    createTupleEncoder,
+    createVecDecoder,
+    createVecEncoder,
+    decodeBool,
+    decodeStr,
+    decodeU32,
+    decodeU128,
+    encodeStr,
+    encodeU16,
+    encodeU32,
+    encodeU128,
+    Enum,
+    Option,
+    WalkerImpl,
+} from "@scale-codec/core";
+import main, { parseInput } from "../src/raffle";
+
+type Request = {
+    era: number,
+    nbWinners: number,
+    excluded: string[],
+    participants: Option<[string, bigint][]>,
+    rewards: Option<string>,
+}
+
+const encodeRequest = createStructEncoder<Request>([
+    ['era', encodeU32],
+    ['nbWinners', encodeU16],
+    ['excluded', createVecEncoder(encodeStr)],
+    ['participants', createOptionEncoder(createVecEncoder(createTupleEncoder<[string, bigint]>([encodeStr, encodeU128])))],
+    ['rewards', createOptionEncoder(encodeStr)],
+]);
+
+type Response = {
+    era: number,
+    skipped: boolean,
+    rewards: bigint,
+    winners: string[],
+    skipReason: Option<string>,
+    winnerStakes: Option<bigint[]>,
+}
+
+const decodeResponse = createStructDecoder<Response>([
+    ['era', decodeU32],
+    ['skipped', decodeBool],
+    ['rewards', decodeU128],
+    ['winners', createVecDecoder(decodeStr)],
+    ['skipReason', createOptionDecoder(decodeStr)],
+    ['winnerStakes', createOptionDecoder(createVecDecoder(decodeU128))],
+]);
+
+const ALICE = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+const BOB = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+
+function toHex(request: Request): `0x${string}` {
+    return `0x${Buffer.from(WalkerImpl.encode(request, encodeRequest)).toString("hex")}`;
+}
+
+// answers the queries sent to the indexer, and records them
+function mockIndexer(subPeriod: string): string[] {
+    const queries: string[] = [];
+    (globalThis as any).pink = {
+        batchHttpRequest: (requests: { body: string }[]) => requests.map(({ body }) => {
+            const query = Buffer.from(body.slice(2), "hex").toString();
+            queries.push(query);
+            let data;
+            if (query.includes("dAppStakingEras")) {
+                data = { dAppStakingEras: { nodes: [{ era: 4517, blockNumber: 100 }] } };
+            } else if (query.includes("dAppSubPeriods")) {
+                data = { dAppSubPeriods: { nodes: [{ period: 2, subPeriod, blockNumber: 90 }] } };
+            } else if (query.includes("dAppRewards")) {
+                data = { dAppRewards: { nodes: [{ amount: "1000", era: 4517 }] } };
+            } else {
+                data = { stakes: { groupedAggregates: [{ sum: { amount: "1000000000000000" }, keys: [ALICE] }] } };
+            }
+            return { statusCode: 200, body: JSON.stringify({ data }) };
+        }),
+    };
+    return queries;
+}
+
+test("parse the participants and the rewards of the request", () => {
+    const request: Request = {
+        era: 4517,
+        nbWinners: 1,
+        excluded: [ALICE],
+        participants: Enum.variant('Some', [[BOB, BigInt(1000)]]),
+        rewards: Enum.variant('Some', "163483092786717962675"),
+    };
+    const input = parseInput(toHex(request));
+    assert.equal(input.era, 4517);
+    assert.equal(input.nbWinners, 1);
+    assert.deepEqual(input.excluded, [ALICE]);
+    assert.deepEqual(input.participants.as('Some'), [[BOB, BigInt(1000)]]);
+    assert.equal(input.rewards.as('Some'), "163483092786717962675");
+
+    const empty = parseInput(toHex({ ...request, participants: Enum.variant('None'), rewards: Enum.variant('None') }));
+    assert.equal(empty.participants.tag, 'None');
+    assert.equal(empty.rewards.tag, 'None');
+});
+
+test("use the participants and the rewards read in the anchor", () => {
+    const queries = mockIndexer("BuildAndEarn");
+    const request: Request = {
+        era: 4517,
+        nbWinners: 1,
+        excluded: [ALICE],
+        participants: Enum.variant('Some', [[ALICE, BigInt("1000000000000000")], [BOB, BigInt("2000000000000000")]]),
+        rewards: Enum.variant('Some', "163483092786717962675"),
+    };
+    const output = WalkerImpl.decode(main(toHex(request), "https://indexer.local"), decodeResponse);
+
+    assert.equal(output.era, 4517);
+    assert.equal(output.skipped, false);
+    assert.equal(output.rewards, BigInt("163483092786717962675"));
+    assert.deepEqual(output.winners, [BOB]);
+    assert.equal(output.skipReason.tag, 'None');
+    assert.deepEqual(output.winnerStakes.as('Some'), [BigInt("2000000000000000")]);
+    // the indexer is only queried for the sub-period
+    assert.ok(queries.every((query) => !query.includes("dAppRewards") && !query.includes("stakes")));
+});
+
+test("query the indexer when the anchor doesn't provide the participants", () => {
+    const queries = mockIndexer("BuildAndEarn");
+    const request: Request = {
+        era: 4517,
+        nbWinners: 1,
+        excluded: [],
+        participants: Enum.variant('None'),
+        rewards: Enum.variant('None'),
+    };
+    const output = WalkerImpl.decode(main(toHex(request), "https://indexer.local"), decodeResponse);
+
+    assert.equal(output.rewards, BigInt(1000));
+    assert.deepEqual(output.winners, [ALICE]);
+    assert.deepEqual(output.winnerStakes.as('Some'), [BigInt("1000000000000000")]);
+    assert.ok(queries.some((query) => query.includes("dAppRewards")));
+    assert.ok(queries.some((query) => query.includes("stakes")));
+});
+
+test("skip the raffle during the voting sub-period", () => {
+    mockIndexer("Voting");
+    const request: Request = {
+        era: 4517,
+        nbWinners: 1,
+        excluded: [],
+        participants: Enum.variant('None'),
+        rewards: Enum.variant('None'),
+    };
+    const output = WalkerImpl.decode(main(toHex(request), "https://indexer.local"), decodeResponse);
+
+    assert.equal(output.skipped, true);
+    assert.deepEqual(output.winners, []);
+    assert.equal(output.skipReason.as('Some'), "voting sub-period");
+    assert.equal(output.winnerStakes.tag, 'None');
+});